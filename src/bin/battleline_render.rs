@@ -0,0 +1,38 @@
+//! Draws a position given in the notation of `GameState::to_notation`.
+//!
+//! Usage: `battleline_render [--ansi] [NOTATION]`, reading the notation from stdin when it is
+//! not given as arguments.
+
+extern crate rusty_battleline_interface as rbi;
+
+use rbi::game_state::GameState;
+use rbi::render::{self, RenderOptions};
+use std::env;
+use std::io::{self, Read};
+use std::process;
+
+fn main() {
+    let mut options = RenderOptions::default();
+    let mut words = vec![];
+    for arg in env::args().skip(1) {
+        if arg == "--ansi" {
+            options.ansi = true;
+        } else {
+            words.push(arg);
+        }
+    }
+    let mut notation = words.join(" ");
+    if notation.is_empty() {
+        if let Err(e) = io::stdin().read_to_string(&mut notation) {
+            eprintln!("failed to read stdin: {}", e);
+            process::exit(1);
+        }
+    }
+    match GameState::from_notation(&notation) {
+        Ok(state) => print!("{}", render::render(&state, &options)),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use formation;
+use game_state::{FlagIndex, GameState};
+use message_parsing::{Card, ClaimStatus, Color, Direction};
+
+/// Highest card number in the deck. Every color runs from 1 up to it.
+pub const MAX_NUMBER: i32 = 9;
+/// Cards a player holds while there is still something to draw.
+pub const HAND_SIZE: usize = 7;
+
+/// How a game was won.
+#[derive(PartialEq, Debug, Clone)]
+pub enum WinReason {
+    /// Five of the nine flags.
+    Envelopment,
+    /// Three adjacent flags.
+    Breakthrough,
+    /// Nobody could play any more without either of the above, so the player holding more flags
+    /// wins, and equal flags are a draw. A player stuck with cards in hand has filled their
+    /// side of every open flag, and one with none left has filled every slot a claim did not
+    /// close, so with the full deck all nine flags end up claimed and five of them decide the
+    /// game first. It takes a shorter deck given to `Referee::new`.
+    MostFlags,
+    /// The other player broke the protocol, described by the message.
+    Forfeit(String),
+}
+
+pub fn other_direction(direction: Direction) -> Direction {
+    match direction {
+        Direction::North => Direction::South,
+        Direction::South => Direction::North,
+    }
+}
+
+/// How a flag held by `direction` shows in the claim status.
+pub fn claim_for(direction: Direction) -> ClaimStatus {
+    match direction {
+        Direction::North => ClaimStatus::North,
+        Direction::South => ClaimStatus::South,
+    }
+}
+
+/// The 54 cards of a game, in the order `GameHandler` builds its deck from `colors`.
+pub fn full_deck() -> Vec<Card> {
+    let mut deck = vec![];
+    for number in 1..MAX_NUMBER + 1 {
+        for index in 0..6 {
+            if let Some(color) = Color::from_index(index) {
+                deck.push(Card {
+                    color,
+                    number,
+                });
+            }
+        }
+    }
+    deck
+}
+
+/// Both sides of the nine flags and who holds them, seen from above the table.
+///
+/// Flags are settled by `resolve_flags` rather than by explicit claims: a flag goes to a player
+/// as soon as their completed side can no longer be beaten.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Board {
+    pub north: Vec<Vec<Card>>,
+    pub south: Vec<Vec<Card>>,
+    pub claims: Vec<ClaimStatus>,
+    completed_first: Vec<Option<Direction>>,
+}
+
+impl Default for Board {
+    fn default() -> Board {
+        Board {
+            north: vec![vec![]; 9],
+            south: vec![vec![]; 9],
+            claims: vec![ClaimStatus::Unclaimed; 9],
+            completed_first: vec![None; 9],
+        }
+    }
+}
+
+impl Board {
+    /// The board `state` describes, with its claims as they stand. The order the cards went
+    /// down in is lost, so when both sides of an unclaimed flag are complete the opponent is
+    /// taken to have finished first.
+    pub fn from_state(state: &GameState) -> Board {
+        let mut board: Board = Default::default();
+        for flag in 0..board.claims.len() {
+            for card in state.opponent_side.get(flag).into_iter().flatten() {
+                board.play(state.opponent_direction, flag, *card);
+            }
+            for card in state.player_side.get(flag).into_iter().flatten() {
+                board.play(state.player_direction, flag, *card);
+            }
+            board.claims[flag] =
+                state.claim_status.get(flag).cloned().unwrap_or(ClaimStatus::Unclaimed);
+        }
+        board
+    }
+    pub fn side(&self, direction: Direction) -> &Vec<Vec<Card>> {
+        match direction {
+            Direction::North => &self.north,
+            Direction::South => &self.south,
+        }
+    }
+    fn side_mut(&mut self, direction: Direction) -> &mut Vec<Vec<Card>> {
+        match direction {
+            Direction::North => &mut self.north,
+            Direction::South => &mut self.south,
+        }
+    }
+    /// True while `direction` may still play to `flag`.
+    pub fn is_open(&self, direction: Direction, flag: FlagIndex) -> bool {
+        self.side(direction).get(flag).is_some_and(|cards| cards.len() < 3) &&
+        self.claims.get(flag) == Some(&ClaimStatus::Unclaimed)
+    }
+    pub fn open_flags(&self, direction: Direction) -> Vec<FlagIndex> {
+        (0..self.claims.len()).filter(|flag| self.is_open(direction, *flag)).collect()
+    }
+    /// Places `card` on `direction`'s side of `flag`. The caller checks the move is legal.
+    pub fn play(&mut self, direction: Direction, flag: FlagIndex, card: Card) {
+        self.side_mut(direction)[flag].push(card);
+        if self.side(direction)[flag].len() == 3 && self.completed_first[flag].is_none() {
+            self.completed_first[flag] = Some(direction);
+        }
+    }
+    /// Awards every flag that can be decided now, given that the cards in `unplayed` are all
+    /// that can still reach the board. Returns the flags that changed hands.
+    pub fn resolve_flags(&mut self, unplayed: &[Card]) -> Vec<FlagIndex> {
+        let mut resolved = vec![];
+        for flag in 0..self.claims.len() {
+            if self.claims[flag] != ClaimStatus::Unclaimed {
+                continue;
+            }
+            for direction in &[Direction::North, Direction::South] {
+                if self.wins_flag(*direction, flag, unplayed) {
+                    self.claims[flag] = claim_for(*direction);
+                    resolved.push(flag);
+                    break;
+                }
+            }
+        }
+        resolved
+    }
+    fn wins_flag(&self, direction: Direction, flag: FlagIndex, unplayed: &[Card]) -> bool {
+        let strength = match formation::classify_side(&self.side(direction)[flag]) {
+            Some(strength) => strength,
+            None => return false,
+        };
+        let opposing = &self.side(other_direction(direction))[flag];
+        match formation::classify_side(opposing) {
+            Some(other) => {
+                strength > other ||
+                (strength == other && self.completed_first[flag] == Some(direction))
+            }
+            None => {
+                formation::best_reachable_formation(opposing, unplayed)
+                    .is_none_or(|best| best.strength <= strength)
+            }
+        }
+    }
+    /// The winner by five flags or three adjacent flags, if the game is over.
+    pub fn winner(&self) -> Option<(Direction, WinReason)> {
+        for direction in &[Direction::North, Direction::South] {
+            let claim = claim_for(*direction);
+            if self.claims.windows(3).any(|flags| flags.iter().all(|c| *c == claim)) {
+                return Some((*direction, WinReason::Breakthrough));
+            }
+            if self.claims.iter().filter(|c| **c == claim).count() >= 5 {
+                return Some((*direction, WinReason::Envelopment));
+            }
+        }
+        None
+    }
+    /// Whether the game is over, and if so its winner, `None` for a draw, and why. `both_stuck`
+    /// says whether neither player can move, and is only asked once nobody has won outright.
+    pub fn game_over<F>(&self, both_stuck: F) -> Option<(Option<Direction>, WinReason)>
+        where F: FnOnce() -> bool
+    {
+        if let Some((winner, reason)) = self.winner() {
+            return Some((Some(winner), reason));
+        }
+        if both_stuck() {
+            return Some((self.most_flags(), WinReason::MostFlags));
+        }
+        None
+    }
+    /// The player holding more flags, or `None` on a tie.
+    pub fn most_flags(&self) -> Option<Direction> {
+        let north = self.claims.iter().filter(|c| **c == ClaimStatus::North).count();
+        let south = self.claims.iter().filter(|c| **c == ClaimStatus::South).count();
+        if north > south {
+            Some(Direction::North)
+        } else if south > north {
+            Some(Direction::South)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_board {
+    use super::*;
+    use test_support::card;
+
+    #[test]
+    fn full_deck_has_every_card_once() {
+        let deck = full_deck();
+        assert_eq!(54, deck.len());
+        for (i, card) in deck.iter().enumerate() {
+            assert!(!deck[i + 1..].contains(card));
+        }
+    }
+
+    #[test]
+    fn complete_sides_are_compared() {
+        let mut board: Board = Default::default();
+        board.play(Direction::North, 0, card(Color::Color1, 1));
+        board.play(Direction::North, 0, card(Color::Color2, 5));
+        board.play(Direction::North, 0, card(Color::Color3, 9));
+        board.play(Direction::South, 0, card(Color::Color4, 2));
+        board.play(Direction::South, 0, card(Color::Color4, 3));
+        assert!(board.resolve_flags(&full_deck()).is_empty());
+        board.play(Direction::South, 0, card(Color::Color5, 4));
+        assert_eq!(vec![0], board.resolve_flags(&[]));
+        assert_eq!(ClaimStatus::South, board.claims[0]);
+        assert!(!board.is_open(Direction::North, 0));
+        assert!(!board.is_open(Direction::South, 0));
+    }
+
+    #[test]
+    fn tie_goes_to_the_side_completed_first() {
+        let mut board: Board = Default::default();
+        board.play(Direction::South, 3, card(Color::Color1, 2));
+        board.play(Direction::South, 3, card(Color::Color2, 5));
+        board.play(Direction::South, 3, card(Color::Color3, 9));
+        board.play(Direction::North, 3, card(Color::Color4, 2));
+        board.play(Direction::North, 3, card(Color::Color5, 5));
+        board.play(Direction::North, 3, card(Color::Color6, 9));
+        board.resolve_flags(&[]);
+        assert_eq!(ClaimStatus::South, board.claims[3]);
+    }
+
+    #[test]
+    fn unbeatable_side_is_claimed_early() {
+        let mut board: Board = Default::default();
+        board.play(Direction::North, 2, card(Color::Color1, 7));
+        board.play(Direction::North, 2, card(Color::Color1, 8));
+        board.play(Direction::North, 2, card(Color::Color1, 9));
+        board.play(Direction::South, 2, card(Color::Color2, 1));
+        let unplayed: Vec<Card> = full_deck()
+            .into_iter()
+            .filter(|c| c.color != Color::Color1 || c.number < 7)
+            .filter(|c| *c != card(Color::Color2, 1))
+            .collect();
+        assert_eq!(vec![2], board.resolve_flags(&unplayed));
+        assert_eq!(ClaimStatus::North, board.claims[2]);
+    }
+
+    #[test]
+    fn winners_by_breakthrough_and_envelopment() {
+        let mut board: Board = Default::default();
+        assert_eq!(None, board.winner());
+        board.claims[4] = ClaimStatus::South;
+        board.claims[5] = ClaimStatus::South;
+        board.claims[7] = ClaimStatus::South;
+        assert_eq!(None, board.winner());
+        board.claims[6] = ClaimStatus::South;
+        assert_eq!(Some((Direction::South, WinReason::Breakthrough)), board.winner());
+
+        let mut board: Board = Default::default();
+        for flag in &[0, 2, 4, 6, 8] {
+            board.claims[*flag] = ClaimStatus::North;
+        }
+        assert_eq!(Some((Direction::North, WinReason::Envelopment)), board.winner());
+        assert_eq!(Some(Direction::North), board.most_flags());
+    }
+}
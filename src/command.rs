@@ -0,0 +1,161 @@
+use game_state::GameState;
+use message_parsing::{self, ParseError};
+
+/// A reply the bot sends back to the server.
+///
+/// Cards are kept typed, so turning a command into text needs the color names the server
+/// announced, see `to_wire`.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Command {
+    /// Answers `player <direction> name`.
+    PlayerName {
+        direction: message_parsing::Direction,
+        name: String,
+    },
+    /// Answers `go play-card`. `flag` is numbered 1 to 9 as on the wire.
+    PlayCard {
+        flag: u32,
+        card: message_parsing::Card,
+    },
+}
+
+impl Command {
+    /// Formats the command as the line the server expects, without the trailing newline.
+    pub fn to_wire(&self, state: &GameState) -> String {
+        match *self {
+            Command::PlayerName { direction, ref name } => {
+                format!("player {} {}",
+                        message_parsing::get_direction_string(direction),
+                        name)
+            }
+            Command::PlayCard { flag, card } => {
+                format!("play {} {},{}",
+                        flag,
+                        state.string_from_color(card.color),
+                        card.number)
+            }
+        }
+    }
+
+    /// Parses a line sent by a bot, mapping card colors through the names from the `colors`
+    /// message. This is what a referee uses to check replies.
+    pub fn from_wire(line: &str, colors: &[String]) -> Result<Command, ParseError> {
+        let split: Vec<&str> = line.split_whitespace().collect();
+        match split.first() {
+            None => Err(ParseError::Empty),
+            Some(&"player") => {
+                message_parsing::expect_count(&split, 3, "player <direction> <name>")?;
+                Ok(Command::PlayerName {
+                    direction: message_parsing::convert_direction(split[1], 1)?,
+                    name: String::from(split[2]),
+                })
+            }
+            Some(&"play") => {
+                message_parsing::expect_count(&split, 3, "play <flag 1-9> <card>")?;
+                let flag = message_parsing::convert_flag_number(split[1], 1)?;
+                let card = message_parsing::convert_string_to_card(split[2], 2)?;
+                let color = colors.iter()
+                    .position(|name| *name == card.color)
+                    .and_then(message_parsing::Color::from_index)
+                    .ok_or_else(|| {
+                        message_parsing::invalid(split[2], 2, "<known color>,<number>")
+                    })?;
+                Ok(Command::PlayCard {
+                    flag,
+                    card: message_parsing::Card {
+                        color,
+                        number: card.number,
+                    },
+                })
+            }
+            Some(e) => Err(ParseError::UnknownMessage { token: String::from(*e) }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_command {
+    use super::*;
+    use message_parsing as mp;
+
+    fn state_with_colors() -> GameState {
+        GameState {
+            colors: vec![String::from("red"),
+                         String::from("blue"),
+                         String::from("green"),
+                         String::from("yellow"),
+                         String::from("purple"),
+                         String::from("orange")],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn player_name_command() {
+        let command = Command::PlayerName {
+            direction: mp::Direction::South,
+            name: String::from("rusty_battleline_bot"),
+        };
+        assert_eq!("player south rusty_battleline_bot",
+                   command.to_wire(&state_with_colors()));
+    }
+
+    #[test]
+    fn play_card_command() {
+        let command = Command::PlayCard {
+            flag: 1,
+            card: mp::Card {
+                color: mp::Color::Color1,
+                number: 1,
+            },
+        };
+        assert_eq!("play 1 red,1", command.to_wire(&state_with_colors()));
+        let command = Command::PlayCard {
+            flag: 9,
+            card: mp::Card {
+                color: mp::Color::Color6,
+                number: 8,
+            },
+        };
+        assert_eq!("play 9 orange,8", command.to_wire(&state_with_colors()));
+    }
+
+    #[test]
+    fn parse_commands_from_wire() {
+        let state = state_with_colors();
+        let commands = vec![Command::PlayerName {
+                                direction: mp::Direction::North,
+                                name: String::from("bot"),
+                            },
+                            Command::PlayCard {
+                                flag: 4,
+                                card: mp::Card {
+                                    color: mp::Color::Color5,
+                                    number: 6,
+                                },
+                            }];
+        for command in commands {
+            assert_eq!(Ok(command.clone()),
+                       Command::from_wire(&command.to_wire(&state), &state.colors));
+        }
+    }
+
+    #[test]
+    fn malformed_commands_are_errors() {
+        let colors = state_with_colors().colors;
+        assert_eq!(Err(ParseError::InvalidToken {
+                       token: String::from("pink,3"),
+                       position: 2,
+                       expected: "<known color>,<number>",
+                   }),
+                   Command::from_wire("play 2 pink,3", &colors));
+        assert_eq!(Err(ParseError::InvalidToken {
+                       token: String::from("0"),
+                       position: 1,
+                       expected: "<flag 1-9>",
+                   }),
+                   Command::from_wire("play 0 red,3", &colors));
+        assert_eq!(Err(ParseError::UnknownMessage { token: String::from("claim") }),
+                   Command::from_wire("claim 3", &colors));
+    }
+}
@@ -0,0 +1,48 @@
+use game_state::{AiInterface, GameHandler};
+use std::io::{self, BufRead, Write};
+
+/// Plays one game as a bot: reads server lines from `reader` until EOF, keeps a `GameHandler`
+/// up to date and writes every reply to `writer`, flushing after each line so the server sees
+/// it straight away.
+///
+/// A bot binary only needs
+/// `run_bot(MyAi {}, io::stdin().lock(), io::stdout())`.
+pub fn run_bot<A, R, W>(ai: A, reader: R, writer: W) -> io::Result<()>
+    where A: AiInterface,
+          R: BufRead,
+          W: Write
+{
+    let mut handler = GameHandler::with_output(writer);
+    for line in reader.lines() {
+        handler.run_one_round(&ai, line?)?;
+    }
+    handler.output.flush()
+}
+
+#[cfg(test)]
+mod test_driver {
+    use super::*;
+    use std::io::Cursor;
+    use test_support::FirstMoveBot;
+
+    #[test]
+    fn replies_are_written_in_order() {
+        let input = "player south name\n\
+                     colors a b c d e f\n\
+                     player south hand c,4 d,9\n\
+                     flag claim-status unclaimed unclaimed unclaimed unclaimed unclaimed \
+                     unclaimed unclaimed unclaimed unclaimed\n\
+                     go play-card\n";
+        let mut output = vec![];
+        run_bot(FirstMoveBot, Cursor::new(input), &mut output).unwrap();
+        assert_eq!("player south first\nplay 1 c,4\n",
+                   String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn empty_input_writes_nothing() {
+        let mut output = vec![];
+        run_bot(FirstMoveBot, Cursor::new(""), &mut output).unwrap();
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+//! Scoring positions, for bots that compare the positions their moves lead to.
+//!
+//! Every built-in evaluator scores from our side, between -1 for hopeless and 1 for won, so
+//! that weights in `Weighted` can be compared with each other.
+
+use board;
+use formation::Strength;
+use game_state::{FlagIndex, FlagProof, GameState};
+
+pub trait Evaluator {
+    /// How good `state` is for the player it belongs to. Higher is better.
+    fn evaluate(&self, state: &GameState) -> f64;
+}
+
+/// Who holds a flag, from our side.
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum Holder {
+    Us,
+    Them,
+    Nobody,
+}
+
+fn holder(state: &GameState, flag: FlagIndex) -> Holder {
+    match state.claim_status.get(flag) {
+        Some(claim) if *claim == board::claim_for(state.player_direction) => Holder::Us,
+        Some(claim) if *claim == board::claim_for(state.opponent_direction) => Holder::Them,
+        _ => Holder::Nobody,
+    }
+}
+
+/// Flags held, ours minus theirs, over the nine flags.
+pub struct FlagCount;
+
+impl Evaluator for FlagCount {
+    fn evaluate(&self, state: &GameState) -> f64 {
+        let mut score = 0.0;
+        for flag in 0..state.claim_status.len() {
+            match holder(state, flag) {
+                Holder::Us => score += 1.0,
+                Holder::Them => score -= 1.0,
+                Holder::Nobody => {}
+            }
+        }
+        score / 9.0
+    }
+}
+
+/// Most a lead over the opponent's best formation counts for, so a flag far ahead does not
+/// drown out everything else.
+const MARGIN_CAP: i32 = 40;
+/// Weight of each card on our side of a flag, favouring flags close to completion.
+const PROGRESS: i32 = 10;
+
+/// Puts formations ahead of sums: the best host is still below the worst skirmish line.
+fn value(strength: Strength) -> i32 {
+    strength.formation as i32 * 30 + strength.sum
+}
+
+/// How the unclaimed flags look, comparing the best formation we can still reach on each with
+/// the best the opponent can. A flag we can prove counts 1. One they look set to win counts
+/// between -0.5 and -1, lower the more of our numbers are sunk into it. The rest count up to
+/// 0.5 by our lead and how many cards we have there.
+pub struct FormationStrength;
+
+impl FormationStrength {
+    fn flag(&self, state: &GameState, flag: FlagIndex) -> f64 {
+        let sunk: i32 = state.player_side[flag].iter().map(|card| card.number).sum();
+        let lost = -0.5 - 0.5 * sunk as f64 / 27.0;
+        let ours = match state.player_best_reachable(flag) {
+            Some(reachable) => value(reachable.strength),
+            None => return lost,
+        };
+        // Nothing on their side yet says nothing about what they will build there.
+        let opponent = &state.opponent_side[flag];
+        let theirs = if opponent.is_empty() {
+            0
+        } else {
+            state.opponent_best_reachable(flag).map_or(0, |reachable| value(reachable.strength))
+        };
+        if ours < theirs || (ours == theirs && opponent.len() == 3) {
+            return lost;
+        }
+        if let FlagProof::Proven { .. } = state.can_prove_flag(flag) {
+            return 1.0;
+        }
+        let progress = PROGRESS * state.player_side[flag].len() as i32;
+        0.5 * ((ours - theirs).min(MARGIN_CAP) + progress) as f64 /
+        (MARGIN_CAP + 3 * PROGRESS) as f64
+    }
+}
+
+impl Evaluator for FormationStrength {
+    fn evaluate(&self, state: &GameState) -> f64 {
+        let mut score = 0.0;
+        for flag in 0..state.player_side.len() {
+            if holder(state, flag) == Holder::Nobody {
+                score += self.flag(state, flag);
+            }
+        }
+        score / 9.0
+    }
+}
+
+/// How close each player is to a breakthrough. A run of three adjacent flags the other
+/// player holds none of is worth the square of the flags a player holds in it, out of 9, so
+/// flags next to each other count for more than the same flags apart. The score is our total
+/// minus theirs over the seven runs.
+pub struct ThreeInARow;
+
+impl Evaluator for ThreeInARow {
+    fn evaluate(&self, state: &GameState) -> f64 {
+        let holders: Vec<Holder> = (0..state.claim_status.len())
+            .map(|flag| holder(state, flag))
+            .collect();
+        let potential = |run: &[Holder], player: Holder| {
+            if run.iter().any(|held| *held != player && *held != Holder::Nobody) {
+                return 0.0;
+            }
+            let held = run.iter().filter(|held| **held == player).count() as f64;
+            held * held / 9.0
+        };
+        let mut score = 0.0;
+        for run in holders.windows(3) {
+            score += potential(run, Holder::Us) - potential(run, Holder::Them);
+        }
+        score / 7.0
+    }
+}
+
+/// A weighted sum of other evaluators, so weights can be tuned without touching the bots.
+#[derive(Default)]
+pub struct Weighted {
+    pub terms: Vec<(f64, Box<dyn Evaluator>)>,
+}
+
+impl Weighted {
+    pub fn new() -> Weighted {
+        Default::default()
+    }
+    pub fn with<E: Evaluator + 'static>(mut self, weight: f64, evaluator: E) -> Weighted {
+        self.terms.push((weight, Box::new(evaluator)));
+        self
+    }
+}
+
+impl Evaluator for Weighted {
+    fn evaluate(&self, state: &GameState) -> f64 {
+        self.terms.iter().map(|(weight, evaluator)| weight * evaluator.evaluate(state)).sum()
+    }
+}
+
+#[cfg(test)]
+mod test_evaluation {
+    use super::*;
+
+    const EMPTY: &str = "n red,orange,yellow,green,blue,purple |/|/|/|/|/|/|/|/| --------- a1";
+
+    fn position(flags: &str, claims: &str, hand: &str) -> GameState {
+        GameState::from_notation(&format!("n red,orange,yellow,green,blue,purple {} {} {}",
+                                          flags,
+                                          claims,
+                                          hand))
+            .unwrap()
+    }
+
+    #[test]
+    fn flag_count_is_from_our_side() {
+        let north = position("|/|/|/|/|/|/|/|/|", "nn-s-----", "a1");
+        assert!((FlagCount.evaluate(&north) - 1.0 / 9.0).abs() < 1e-9);
+        let mut south = north.clone();
+        south.player_direction = north.opponent_direction;
+        south.opponent_direction = north.player_direction;
+        assert!((FlagCount.evaluate(&south) + 1.0 / 9.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn formation_strength_prefers_proven_flags() {
+        let empty = GameState::from_notation(EMPTY).unwrap();
+        let proven = position("c7c8c9|/|/|/|/|/|/|/|/|", "---------", "a1");
+        let lost = position("a2b3|d7d8d9/|/|/|/|/|/|/|/|", "---------", "a1");
+        assert!(FormationStrength.evaluate(&proven) > FormationStrength.evaluate(&empty));
+        assert!(FormationStrength.evaluate(&lost) < FormationStrength.evaluate(&empty));
+    }
+
+    #[test]
+    fn three_in_a_row_sees_adjacency() {
+        let together = position("|/|/|/|/|/|/|/|/|", "nn-------", "a1");
+        let apart = position("|/|/|/|/|/|/|/|/|", "n---n----", "a1");
+        assert!(ThreeInARow.evaluate(&together) > ThreeInARow.evaluate(&apart));
+        assert_eq!(0.0, ThreeInARow.evaluate(&GameState::from_notation(EMPTY).unwrap()));
+    }
+
+    #[test]
+    fn weighted_sums_its_terms() {
+        let state = position("|/|/|/|/|/|/|/|/|", "nn-s-----", "a1");
+        let weighted = Weighted::new().with(2.0, FlagCount).with(0.5, ThreeInARow);
+        let expected = 2.0 * FlagCount.evaluate(&state) + 0.5 * ThreeInARow.evaluate(&state);
+        assert!((weighted.evaluate(&state) - expected).abs() < 1e-9);
+        assert_eq!(0.0, Weighted::new().evaluate(&state));
+    }
+}
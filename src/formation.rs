@@ -0,0 +1,355 @@
+use board::MAX_NUMBER;
+use message_parsing::{Card, Color};
+use std::cmp;
+
+/// The Battle Line formations, declared weakest first so the derived ordering ranks them.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Copy, Clone)]
+pub enum Formation {
+    /// Any three cards.
+    Host,
+    /// Three consecutive numbers.
+    SkirmishLine,
+    /// Three cards of one color.
+    Battalion,
+    /// Three cards of one number.
+    Phalanx,
+    /// Three consecutive numbers of one color.
+    Wedge,
+}
+
+/// How strong a completed side is. Orders by formation first and then by the sum of the card
+/// numbers. Equal strengths go to whoever completed their side first, which this type cannot
+/// know about.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Copy, Clone)]
+pub struct Strength {
+    pub formation: Formation,
+    pub sum: i32,
+}
+
+pub fn classify(cards: &[Card; 3]) -> Strength {
+    let mut numbers = [cards[0].number, cards[1].number, cards[2].number];
+    numbers.sort();
+    let same_color = cards[0].color == cards[1].color && cards[1].color == cards[2].color;
+    let consecutive = numbers[1] == numbers[0] + 1 && numbers[2] == numbers[1] + 1;
+    let same_number = numbers[0] == numbers[2];
+    let formation = if same_color && consecutive {
+        Formation::Wedge
+    } else if same_number {
+        Formation::Phalanx
+    } else if same_color {
+        Formation::Battalion
+    } else if consecutive {
+        Formation::SkirmishLine
+    } else {
+        Formation::Host
+    };
+    Strength {
+        formation,
+        sum: numbers.iter().sum(),
+    }
+}
+
+/// Classifies a side of a flag, as stored in `GameState::player_side`, once it has exactly
+/// three cards.
+pub fn classify_side(cards: &[Card]) -> Option<Strength> {
+    match *cards {
+        [a, b, c] => Some(classify(&[a, b, c])),
+        _ => None,
+    }
+}
+
+/// The strongest side a flag can still end up as.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Reachable {
+    pub strength: Strength,
+    /// Cards from the available pool that complete the side, empty if it was already complete.
+    pub needed: Vec<Card>,
+}
+
+/// Finds the best formation `side` can still be completed into using cards from `available`,
+/// or `None` if too few cards are available to complete it.
+///
+/// Rather than trying every combination of available cards, each formation is built directly
+/// from the strongest candidate down and the first formation that can be completed wins.
+pub fn best_reachable_formation(side: &[Card], available: &[Card]) -> Option<Reachable> {
+    if side.len() >= 3 {
+        return classify_side(side).map(|strength| {
+            Reachable {
+                strength,
+                needed: vec![],
+            }
+        });
+    }
+    let searches: [Search; 5] = [wedge, phalanx, battalion, skirmish_line, host];
+    for search in &searches {
+        if let Some(needed) = search(side, available) {
+            let mut cards = side.to_vec();
+            cards.extend(needed.iter().cloned());
+            return classify_side(&cards).map(|strength| {
+                Reachable {
+                    strength,
+                    needed,
+                }
+            });
+        }
+    }
+    None
+}
+
+/// Looks for the cards completing a side as one formation, best sum first.
+type Search = fn(&[Card], &[Card]) -> Option<Vec<Card>>;
+
+/// The colors a side could be built in: its own color if it has cards, else any available one.
+fn candidate_colors(side: &[Card], available: &[Card]) -> Vec<Color> {
+    let mut colors: Vec<Color> = vec![];
+    for card in side.iter().chain(available.iter()) {
+        if !colors.contains(&card.color) {
+            colors.push(card.color);
+        }
+    }
+    match side.first() {
+        Some(first) if side.iter().all(|card| card.color == first.color) => vec![first.color],
+        Some(_) => vec![],
+        None => colors,
+    }
+}
+
+/// Picks one available card for each of `numbers` that also satisfies `accept`.
+fn find_numbers<F>(numbers: &[i32], available: &[Card], accept: F) -> Option<Vec<Card>>
+    where F: Fn(&Card) -> bool
+{
+    numbers.iter()
+        .map(|number| {
+            available.iter()
+                .find(|card| card.number == *number && accept(card))
+                .cloned()
+        })
+        .collect()
+}
+
+/// Numbers of the run starting at `low` still missing from `side`, or `None` when a card of
+/// the side does not fit the run.
+fn missing_from_run(side: &[Card], low: i32) -> Option<Vec<i32>> {
+    let mut missing: Vec<i32> = (low..low + 3).collect();
+    for card in side {
+        let index = missing.iter().position(|number| *number == card.number)?;
+        missing.remove(index);
+    }
+    Some(missing)
+}
+
+fn wedge(side: &[Card], available: &[Card]) -> Option<Vec<Card>> {
+    let colors = candidate_colors(side, available);
+    for low in (1..MAX_NUMBER - 1).rev() {
+        if let Some(missing) = missing_from_run(side, low) {
+            for color in &colors {
+                let needed = find_numbers(&missing, available, |card| card.color == *color);
+                if needed.is_some() {
+                    return needed;
+                }
+            }
+        }
+    }
+    None
+}
+
+fn phalanx(side: &[Card], available: &[Card]) -> Option<Vec<Card>> {
+    let numbers: Vec<i32> = match side.first() {
+        Some(first) if side.iter().all(|card| card.number == first.number) => vec![first.number],
+        Some(_) => return None,
+        None => (1..MAX_NUMBER + 1).rev().collect(),
+    };
+    for number in numbers {
+        let needed: Vec<Card> = available.iter()
+            .filter(|card| card.number == number)
+            .take(3 - side.len())
+            .cloned()
+            .collect();
+        if needed.len() == 3 - side.len() {
+            return Some(needed);
+        }
+    }
+    None
+}
+
+/// The highest `count` cards of `cards`.
+fn highest(cards: &[Card], count: usize) -> Option<Vec<Card>> {
+    if cards.len() < count {
+        return None;
+    }
+    let mut sorted = cards.to_vec();
+    sorted.sort_by_key(|card| cmp::Reverse(card.number));
+    sorted.truncate(count);
+    Some(sorted)
+}
+
+fn battalion(side: &[Card], available: &[Card]) -> Option<Vec<Card>> {
+    candidate_colors(side, available)
+        .iter()
+        .filter_map(|color| {
+            let cards: Vec<Card> =
+                available.iter().filter(|card| card.color == *color).cloned().collect();
+            highest(&cards, 3 - side.len())
+        })
+        .max_by_key(|needed| needed.iter().map(|card| card.number).sum::<i32>())
+}
+
+fn skirmish_line(side: &[Card], available: &[Card]) -> Option<Vec<Card>> {
+    for low in (1..MAX_NUMBER - 1).rev() {
+        if let Some(missing) = missing_from_run(side, low) {
+            if let Some(needed) = find_numbers(&missing, available, |_| true) {
+                return Some(needed);
+            }
+        }
+    }
+    None
+}
+
+fn host(side: &[Card], available: &[Card]) -> Option<Vec<Card>> {
+    highest(available, 3 - side.len())
+}
+
+#[cfg(test)]
+mod test_formation {
+    use super::*;
+    use board::full_deck;
+    use test_support::card;
+
+    #[test]
+    fn classify_each_formation() {
+        let wedge = [card(Color::Color1, 4), card(Color::Color1, 2), card(Color::Color1, 3)];
+        let phalanx = [card(Color::Color1, 7), card(Color::Color2, 7), card(Color::Color3, 7)];
+        let battalion = [card(Color::Color4, 1), card(Color::Color4, 9), card(Color::Color4, 5)];
+        let skirmish = [card(Color::Color1, 8), card(Color::Color2, 9), card(Color::Color1, 7)];
+        let host = [card(Color::Color1, 8), card(Color::Color2, 9), card(Color::Color1, 1)];
+        assert_eq!(Strength {
+                       formation: Formation::Wedge,
+                       sum: 9,
+                   },
+                   classify(&wedge));
+        assert_eq!(Formation::Phalanx, classify(&phalanx).formation);
+        assert_eq!(Formation::Battalion, classify(&battalion).formation);
+        assert_eq!(Formation::SkirmishLine, classify(&skirmish).formation);
+        assert_eq!(Strength {
+                       formation: Formation::Host,
+                       sum: 18,
+                   },
+                   classify(&host));
+    }
+
+    #[test]
+    fn formation_beats_sum() {
+        let low_wedge = classify(&[card(Color::Color1, 1),
+                                   card(Color::Color1, 2),
+                                   card(Color::Color1, 3)]);
+        let high_phalanx = classify(&[card(Color::Color1, 9),
+                                      card(Color::Color2, 9),
+                                      card(Color::Color3, 9)]);
+        let high_host = classify(&[card(Color::Color1, 9),
+                                   card(Color::Color2, 9),
+                                   card(Color::Color3, 8)]);
+        let low_skirmish = classify(&[card(Color::Color1, 1),
+                                      card(Color::Color2, 2),
+                                      card(Color::Color3, 3)]);
+        assert!(low_wedge > high_phalanx);
+        assert!(low_skirmish > high_host);
+    }
+
+    #[test]
+    fn sum_breaks_ties_within_a_formation() {
+        let low = classify(&[card(Color::Color1, 2),
+                             card(Color::Color1, 5),
+                             card(Color::Color1, 9)]);
+        let high = classify(&[card(Color::Color2, 3),
+                              card(Color::Color2, 5),
+                              card(Color::Color2, 9)]);
+        let same = classify(&[card(Color::Color3, 3),
+                              card(Color::Color3, 5),
+                              card(Color::Color3, 9)]);
+        assert!(high > low);
+        assert_eq!(high, same);
+    }
+
+    #[test]
+    fn classify_side_needs_three_cards() {
+        assert_eq!(None, classify_side(&[card(Color::Color1, 2), card(Color::Color1, 3)]));
+        assert_eq!(Some(Formation::Wedge),
+                   classify_side(&[card(Color::Color1, 2),
+                                   card(Color::Color1, 3),
+                                   card(Color::Color1, 4)])
+                       .map(|s| s.formation));
+    }
+
+    fn brute_force_best(side: &[Card], available: &[Card]) -> Option<Strength> {
+        if side.len() == 3 {
+            return classify_side(side);
+        }
+        let mut best = None;
+        for (i, card) in available.iter().enumerate() {
+            let mut extended = side.to_vec();
+            extended.push(*card);
+            best = best.max(brute_force_best(&extended, &available[i + 1..]));
+        }
+        best
+    }
+
+    #[test]
+    fn best_reachable_builds_wedge_from_partial_side() {
+        let deck = full_deck();
+        let side = [card(Color::Color2, 5), card(Color::Color2, 6)];
+        let available: Vec<Card> = deck.iter().filter(|c| !side.contains(c)).cloned().collect();
+        let best = best_reachable_formation(&side, &available).unwrap();
+        assert_eq!(Strength {
+                       formation: Formation::Wedge,
+                       sum: 18,
+                   },
+                   best.strength);
+        assert_eq!(vec![card(Color::Color2, 7)], best.needed);
+    }
+
+    #[test]
+    fn best_reachable_of_complete_side_needs_nothing() {
+        let side = [card(Color::Color1, 2), card(Color::Color5, 2), card(Color::Color3, 2)];
+        let best = best_reachable_formation(&side, &full_deck()).unwrap();
+        assert_eq!(Formation::Phalanx, best.strength.formation);
+        assert!(best.needed.is_empty());
+    }
+
+    #[test]
+    fn best_reachable_is_none_without_enough_cards() {
+        let side = [card(Color::Color1, 2)];
+        assert_eq!(None, best_reachable_formation(&side, &[card(Color::Color1, 3)]));
+    }
+
+    #[test]
+    fn best_reachable_matches_brute_force() {
+        let deck = full_deck();
+        let mut seed: u64 = 11;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for _ in 0..300 {
+            let mut pool = deck.clone();
+            let mut side = vec![];
+            for _ in 0..next(3) {
+                side.push(pool.remove(next(pool.len())));
+            }
+            let keep = next(12) + 1;
+            let mut available = vec![];
+            for _ in 0..keep {
+                available.push(pool.remove(next(pool.len())));
+            }
+            let best = best_reachable_formation(&side, &available);
+            assert_eq!(brute_force_best(&side, &available),
+                       best.as_ref().map(|b| b.strength),
+                       "side {:?} available {:?}",
+                       side,
+                       available);
+            if let Some(best) = best {
+                assert_eq!(3 - side.len(), best.needed.len());
+                assert!(best.needed.iter().all(|c| available.contains(c)));
+            }
+        }
+    }
+}
@@ -1,11 +1,73 @@
+use command;
+use formation;
+use inference;
 use message_parsing;
+use transcript;
+use std::io::{self, Write};
+
+/// Index of a flag into `GameState::player_side`, `opponent_side` and `claim_status`, 0 to 8.
+pub type FlagIndex = usize;
+
+/// Playing `card` from the hand onto our side of `flag`.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Move {
+    pub flag: FlagIndex,
+    pub card: message_parsing::Card,
+}
+
+impl Move {
+    pub fn to_command(&self) -> command::Command {
+        command::Command::PlayCard {
+            flag: self.flag as u32 + 1,
+            card: self.card,
+        }
+    }
+}
+
+/// Outcome of `GameState::can_prove_flag`.
+#[derive(PartialEq, Debug, Clone)]
+pub enum FlagProof {
+    /// Our side of the flag does not have three cards yet.
+    Incomplete,
+    /// No completion of the opponent's side beats ours. `opponent_best` is the strongest side
+    /// they can still reach, or `None` when too few unseen cards are left to finish it.
+    Proven {
+        player: formation::Strength,
+        opponent_best: Option<formation::Strength>,
+    },
+    /// The opponent can still finish their side as `cards` and beat ours.
+    CounterExample {
+        cards: Vec<message_parsing::Card>,
+        strength: formation::Strength,
+    },
+}
 
 pub trait AiInterface {
-    fn update_game_state(&self, state: &GameState) -> String;
+    /// Answers `go play-card` with the line to send, such as `play 3 red,7`. Bots that
+    /// implement `choose_move` instead can leave this empty.
+    fn update_game_state(&self, _state: &GameState) -> String {
+        String::new()
+    }
+    /// Picks the move to answer `go play-card` with, or `None` to leave it to `GameHandler`.
+    /// The handler only asks when at least one legal move exists, and checks the answer with
+    /// `GameState::legal_or_fallback` before replying. Without an answer it plays the first
+    /// legal move. By default this parses the line from `update_game_state`.
+    fn choose_move(&self, state: &GameState) -> Option<Move> {
+        match command::Command::from_wire(&self.update_game_state(state), &state.colors) {
+            Ok(command::Command::PlayCard { flag, card }) => {
+                Some(Move {
+                    flag: flag as FlagIndex - 1,
+                    card,
+                })
+            }
+            _ => None,
+        }
+    }
     fn get_bot_name(&self) -> String;
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct GameState {
     pub player_direction: message_parsing::Direction,
     pub opponent_direction: message_parsing::Direction,
@@ -16,6 +78,7 @@ pub struct GameState {
     pub opponent_side: Vec<Vec<message_parsing::Card>>,
     pub player_side: Vec<Vec<message_parsing::Card>>,
     pub player_hand: Vec<message_parsing::Card>,
+    pub opponent_model: inference::OpponentModel,
 }
 
 impl GameState {
@@ -60,22 +123,159 @@ impl GameState {
         }
         card_vec
     }
+    /// True while our side of `flag` has room for another card and nobody has claimed it.
+    pub fn is_flag_open(&self, flag: FlagIndex) -> bool {
+        let has_room = self.player_side.get(flag).is_some_and(|cards| cards.len() < 3);
+        let unclaimed = self.claim_status
+            .get(flag)
+            .is_none_or(|claim| *claim == message_parsing::ClaimStatus::Unclaimed);
+        has_room && unclaimed
+    }
+    pub fn is_legal_move(&self, proposed: &Move) -> bool {
+        self.player_hand.contains(&proposed.card) && self.is_flag_open(proposed.flag)
+    }
+    /// Returns `proposed` if it is legal, otherwise the first legal move found, or `None` when
+    /// nothing is legal.
+    pub fn legal_or_fallback(&self, proposed: Move) -> Option<Move> {
+        if self.is_legal_move(&proposed) {
+            return Some(proposed);
+        }
+        self.legal_moves().next()
+    }
+    /// Checks whether `flag` can be claimed now: our side is complete and no completion of
+    /// the opponent's side using the unseen cards in `deck` beats it. A tie only counts against
+    /// us when the opponent's side is already complete, since otherwise we finished first.
+    pub fn can_prove_flag(&self, flag: FlagIndex) -> FlagProof {
+        let player = match self.player_side
+            .get(flag)
+            .and_then(|cards| formation::classify_side(cards)) {
+            Some(strength) => strength,
+            None => return FlagProof::Incomplete,
+        };
+        let opponent = self.opponent_side.get(flag).cloned().unwrap_or_default();
+        if let Some(strength) = formation::classify_side(&opponent) {
+            return if strength >= player {
+                FlagProof::CounterExample {
+                    cards: opponent,
+                    strength,
+                }
+            } else {
+                FlagProof::Proven {
+                    player,
+                    opponent_best: Some(strength),
+                }
+            };
+        }
+        match formation::best_reachable_formation(&opponent, &self.deck) {
+            Some(best) if best.strength > player => {
+                let mut cards = opponent;
+                cards.extend(best.needed);
+                FlagProof::CounterExample {
+                    cards,
+                    strength: best.strength,
+                }
+            }
+            best => {
+                FlagProof::Proven {
+                    player,
+                    opponent_best: best.map(|best| best.strength),
+                }
+            }
+        }
+    }
+    /// The best formation our side of `flag` can still reach with the cards in our hand and the
+    /// unseen cards in `deck`.
+    pub fn player_best_reachable(&self, flag: FlagIndex) -> Option<formation::Reachable> {
+        let mut available = self.player_hand.clone();
+        available.extend(self.deck.iter().cloned());
+        formation::best_reachable_formation(self.player_side.get(flag)?, &available)
+    }
+    /// The best formation the opponent's side of `flag` can still reach with the unseen cards in
+    /// `deck`.
+    pub fn opponent_best_reachable(&self, flag: FlagIndex) -> Option<formation::Reachable> {
+        formation::best_reachable_formation(self.opponent_side.get(flag)?, &self.deck)
+    }
+    /// Every card in `player_hand` paired with every open flag, card by card in hand order.
+    pub fn legal_moves(&self) -> impl Iterator<Item = Move> + '_ {
+        self.player_hand.iter().flat_map(move |card| {
+            (0..self.player_side.len())
+                .filter(move |flag| self.is_flag_open(*flag))
+                .map(move |flag| {
+                    Move {
+                        flag,
+                        card: *card,
+                    }
+                })
+        })
+    }
 }
 
-#[derive(Default)]
-pub struct GameHandler {
+/// Keeps a `GameState` up to date from the server's lines and answers them on behalf of an
+/// `AiInterface`. Replies go to `output`, stdout unless built with `with_output`.
+pub struct GameHandler<W: Write = io::Stdout> {
     pub state: GameState,
+    pub output: W,
+    recorder: Option<transcript::Recorder>,
 }
 
+impl Default for GameHandler {
+    fn default() -> GameHandler {
+        GameHandler::with_output(io::stdout())
+    }
+}
 
-impl GameHandler {
-    pub fn run_one_round(&mut self, ai: &AiInterface, message: String) {
+impl<W: Write> GameHandler<W> {
+    pub fn with_output(output: W) -> GameHandler<W> {
+        GameHandler {
+            state: Default::default(),
+            output,
+            recorder: None,
+        }
+    }
+    /// Records every line handled from now on, and every reply, as a transcript in `sink`.
+    /// The sink is `Send` so a recording handler can still be moved to another thread.
+    pub fn record_to(&mut self, sink: Box<dyn Write + Send>) {
+        self.recorder = Some(transcript::Recorder::new(sink));
+    }
+    /// Handles one server line, writes the reply to `output` and returns the line written.
+    pub fn run_one_round(&mut self,
+                         ai: &dyn AiInterface,
+                         message: String)
+                         -> io::Result<Option<String>> {
+        let line = self.respond(ai, message).map(|reply| reply.to_wire(&self.state));
+        if let Some(ref line) = line {
+            writeln!(self.output, "{}", line)?;
+            self.output.flush()?;
+        }
+        Ok(line)
+    }
+    /// Updates the state from one server line and returns the reply it calls for, without
+    /// writing anything but the transcript.
+    pub fn respond(&mut self, ai: &dyn AiInterface, message: String) -> Option<command::Command> {
+        if let Some(ref mut recorder) = self.recorder {
+            recorder.record(transcript::Flow::Incoming, &message);
+        }
+        let reply = self.handle(ai, message);
+        if let (Some(recorder), Some(reply)) = (self.recorder.as_mut(), reply.as_ref()) {
+            recorder.record(transcript::Flow::Outgoing, &reply.to_wire(&self.state));
+        }
+        reply
+    }
+    fn handle(&mut self, ai: &dyn AiInterface, message: String) -> Option<command::Command> {
+        let mut reply = None;
         let x = message_parsing::parse_message(message);
         match x {
-            message_parsing::Message::OpponentPlay { number: _, card } => {
+            message_parsing::Message::OpponentPlay { number, card } => {
                 let card = self.state.convert_card_string_to_card(&card);
                 if let Some(index) = self.state.deck.iter().position(|i| *i == card) {
                     self.state.deck.remove(index);
+                    let mut side = self.state
+                        .opponent_side
+                        .get((number - 1) as usize)
+                        .cloned()
+                        .unwrap_or_default();
+                    side.push(card);
+                    self.state.opponent_model.observe_play(&side, &self.state.deck);
                 }
             }
             message_parsing::Message::PlayerHand { direction: _, cards } => {
@@ -89,12 +289,14 @@ impl GameHandler {
             }
             message_parsing::Message::FlagStatus { flag_num: num, direction, cards } => {
                 let cards = self.state.convert_vector_card_string_to_cards(&cards);
+                let mut revealed = vec![];
                 for card in &cards {
                     if let Some(index) = self.state.deck.iter().position(|i| *i == *card) {
                         self.state.deck.remove(index);
+                        revealed.push(*card);
                     }
                 }
-                if self.state.player_side.len() == 0 || self.state.opponent_side.len() == 0 {
+                if self.state.player_side.is_empty() || self.state.opponent_side.is_empty() {
                     for _ in 1..10 {
                         self.state.player_side.push(vec![]);
                         self.state.opponent_side.push(vec![]);
@@ -103,6 +305,12 @@ impl GameHandler {
                 if direction == self.state.player_direction {
                     self.state.player_side[(num - 1) as usize] = cards;
                 } else {
+                    // Cards we never saw played still tell us what the opponent was building.
+                    for card in &revealed {
+                        let position = cards.iter().position(|i| i == card).unwrap_or(0);
+                        let side = &cards[..position + 1];
+                        self.state.opponent_model.observe_play(side, &self.state.deck);
+                    }
                     self.state.opponent_side[(num - 1) as usize] = cards;
                 }
             }
@@ -120,9 +328,10 @@ impl GameHandler {
                 } else {
                     message_parsing::Direction::South
                 };
-                println!("player {} {}",
-                         message_parsing::get_direction_string(direction),
-                         ai.get_bot_name());
+                reply = Some(command::Command::PlayerName {
+                    direction,
+                    name: ai.get_bot_name(),
+                });
             }
             message_parsing::Message::ColorNames { colors } => {
                 for _ in 1..10 {
@@ -140,22 +349,25 @@ impl GameHandler {
                     }
                 }
                 for i in 1..10 {
-                    for x in colors.to_vec() {
-                        let temp = self.state.color_from_string(&x);
-                        {
-                            self.state.deck.push(message_parsing::Card {
-                                color: temp,
-                                number: i,
-                            })
-                        };
+                    for x in &colors {
+                        let temp = self.state.color_from_string(x);
+                        self.state.deck.push(message_parsing::Card {
+                            color: temp,
+                            number: i,
+                        });
                     }
                 }
             }
-            message_parsing::Message::PlayCard => {
-                println!("{}", ai.update_game_state(&self.state));
+            // With nothing legal to play there is no sensible reply, so the AI is not asked.
+            message_parsing::Message::PlayCard if self.state.legal_moves().next().is_some() => {
+                reply = ai.choose_move(&self.state)
+                    .and_then(|proposed| self.state.legal_or_fallback(proposed))
+                    .or_else(|| self.state.legal_moves().next())
+                    .map(|chosen| chosen.to_command());
             }
             _ => {}
         }
+        reply
     }
 }
 
@@ -164,17 +376,24 @@ mod test_game_state {
     use super::*;
     use message_parsing;
     use message_parsing as mp;
+    use test_support::card;
 
     struct TestAi {
     }
 
     impl AiInterface for TestAi {
-        fn update_game_state(&self, _: &GameState) -> String {
-            return String::from("play 1 red,1");
+        fn choose_move(&self, _: &GameState) -> Option<Move> {
+            Some(Move {
+                flag: 0,
+                card: mp::Card {
+                    color: mp::Color::Color1,
+                    number: 1,
+                },
+            })
         }
 
         fn get_bot_name(&self) -> String {
-            return String::from("rusty_battleline_bot");
+            String::from("rusty_battleline_bot")
         }
     }
 
@@ -182,10 +401,10 @@ mod test_game_state {
     fn starter_player_check() {
         let mut handler: GameHandler = Default::default();
         let ai = TestAi {};
-        handler.run_one_round(&ai, String::from("player north name"));
+        handler.run_one_round(&ai, String::from("player north name")).unwrap();
         assert!(message_parsing::Direction::North == handler.state.player_direction);
         assert!(message_parsing::Direction::South == handler.state.opponent_direction);
-        handler.run_one_round(&ai, String::from("player south name"));
+        handler.run_one_round(&ai, String::from("player south name")).unwrap();
         assert!(message_parsing::Direction::South == handler.state.player_direction);
         assert!(message_parsing::Direction::North == handler.state.opponent_direction);
     }
@@ -206,7 +425,7 @@ mod test_game_state {
                               mp::Color::Color4,
                               mp::Color::Color5,
                               mp::Color::Color6];
-        handler.run_one_round(&ai, String::from("colors a b c d e f"));
+        handler.run_one_round(&ai, String::from("colors a b c d e f")).unwrap();
         assert_eq!(54, handler.state.deck.len());
         assert_eq!(colors, handler.state.colors);
         assert_eq!(colors_vec, handler.state.colors_vec);
@@ -224,7 +443,8 @@ mod test_game_state {
                           ];
         handler.run_one_round(&ai,
                               String::from("flag claim-status north south south north unclaimed \
-                                            unclaimed north south north"));
+                                            unclaimed north south north"))
+            .unwrap();
         assert_eq!(9, handler.state.claim_status.len());
         assert_eq!(claims, handler.state.claim_status);
     }
@@ -245,9 +465,9 @@ mod test_game_state {
                                color: mp::Color::Color2,
                                number: 7,
                            }];
-        handler.run_one_round(&ai, String::from("colors red blue 3 4 5 6"));
-        handler.run_one_round(&ai, String::from("player north name"));
-        handler.run_one_round(&ai, String::from("flag 1 cards north red,3 blue,7"));
+        handler.run_one_round(&ai, String::from("colors red blue 3 4 5 6")).unwrap();
+        handler.run_one_round(&ai, String::from("player north name")).unwrap();
+        handler.run_one_round(&ai, String::from("flag 1 cards north red,3 blue,7")).unwrap();
         assert_eq!(expected, handler.state.player_side);
     }
 
@@ -267,9 +487,9 @@ mod test_game_state {
                                color: mp::Color::Color2,
                                number: 7,
                            }];
-        handler.run_one_round(&ai, String::from("colors red blue 3 4 5 6"));
-        handler.run_one_round(&ai, String::from("player north name"));
-        handler.run_one_round(&ai, String::from("flag 2 cards south red,3 blue,7"));
+        handler.run_one_round(&ai, String::from("colors red blue 3 4 5 6")).unwrap();
+        handler.run_one_round(&ai, String::from("player north name")).unwrap();
+        handler.run_one_round(&ai, String::from("flag 2 cards south red,3 blue,7")).unwrap();
         assert_eq!(expected, handler.state.opponent_side);
     }
 
@@ -281,9 +501,9 @@ mod test_game_state {
             color: mp::Color::Color1,
             number: 7,
         };
-        handler.run_one_round(&ai, String::from("colors a b c d e f"));
+        handler.run_one_round(&ai, String::from("colors a b c d e f")).unwrap();
         assert!(handler.state.deck.contains(&expected_card));
-        handler.run_one_round(&ai, String::from("opponent play 1 a,7"));
+        handler.run_one_round(&ai, String::from("opponent play 1 a,7")).unwrap();
         assert_eq!(53, handler.state.deck.len());
         assert!(!handler.state.deck.contains(&expected_card));
     }
@@ -300,15 +520,15 @@ mod test_game_state {
                                       color: mp::Color::Color3,
                                       number: 3,
                                   }];
-        handler.run_one_round(&ai, String::from("colors a b c d e f"));
-        handler.run_one_round(&ai, String::from("player north name"));
+        handler.run_one_round(&ai, String::from("colors a b c d e f")).unwrap();
+        handler.run_one_round(&ai, String::from("player north name")).unwrap();
         for x in &expected_cards {
-            assert!(handler.state.deck.contains(&x));
+            assert!(handler.state.deck.contains(x));
         }
-        handler.run_one_round(&ai, String::from("player north hand a,7 c,3"));
+        handler.run_one_round(&ai, String::from("player north hand a,7 c,3")).unwrap();
         assert_eq!(52, handler.state.deck.len());
         for x in &expected_cards {
-            assert!(!handler.state.deck.contains(&x));
+            assert!(!handler.state.deck.contains(x));
         }
     }
 
@@ -324,9 +544,9 @@ mod test_game_state {
                                       color: mp::Color::Color3,
                                       number: 3,
                                   }];
-        handler.run_one_round(&ai, String::from("colors a b c d e f"));
-        handler.run_one_round(&ai, String::from("player north name"));
-        handler.run_one_round(&ai, String::from("player north hand a,7 c,3"));
+        handler.run_one_round(&ai, String::from("colors a b c d e f")).unwrap();
+        handler.run_one_round(&ai, String::from("player north name")).unwrap();
+        handler.run_one_round(&ai, String::from("player north hand a,7 c,3")).unwrap();
         assert_eq!(expected_cards, handler.state.player_hand);
     }
 
@@ -342,15 +562,15 @@ mod test_game_state {
                                       color: mp::Color::Color3,
                                       number: 3,
                                   }];
-        handler.run_one_round(&ai, String::from("colors a b c d e f"));
-        handler.run_one_round(&ai, String::from("player north name"));
+        handler.run_one_round(&ai, String::from("colors a b c d e f")).unwrap();
+        handler.run_one_round(&ai, String::from("player north name")).unwrap();
         for x in &expected_cards {
-            assert!(handler.state.deck.contains(&x));
+            assert!(handler.state.deck.contains(x));
         }
-        handler.run_one_round(&ai, String::from("flag 1 cards north a,7 c,3"));
+        handler.run_one_round(&ai, String::from("flag 1 cards north a,7 c,3")).unwrap();
         assert_eq!(52, handler.state.deck.len());
         for x in &expected_cards {
-            assert!(!handler.state.deck.contains(&x));
+            assert!(!handler.state.deck.contains(x));
         }
     }
 
@@ -358,11 +578,324 @@ mod test_game_state {
     fn default_vectors_after_certain_commands() {
         let mut handler: GameHandler = Default::default();
         let ai = TestAi {};
-        handler.run_one_round(&ai, String::from("colors a b c d e f"));
+        handler.run_one_round(&ai, String::from("colors a b c d e f")).unwrap();
         assert_eq!(54, handler.state.deck.len());
         assert_eq!(6, handler.state.colors.len());
         assert_eq!(9, handler.state.claim_status.len());
         assert_eq!(9, handler.state.opponent_side.len());
         assert_eq!(9, handler.state.player_side.len());
     }
+
+    fn started_handler() -> GameHandler {
+        let mut handler: GameHandler = Default::default();
+        let ai = TestAi {};
+        handler.run_one_round(&ai, String::from("colors a b c d e f")).unwrap();
+        handler.run_one_round(&ai, String::from("player north name")).unwrap();
+        handler.run_one_round(&ai, String::from("player north hand a,1 b,5")).unwrap();
+        handler
+    }
+
+    #[test]
+    fn legal_move_is_kept() {
+        let handler = started_handler();
+        let proposed = Move {
+            flag: 4,
+            card: card(mp::Color::Color2, 5),
+        };
+        assert!(handler.state.is_legal_move(&proposed));
+        assert_eq!(Some(proposed), handler.state.legal_or_fallback(proposed));
+    }
+
+    #[test]
+    fn card_not_in_hand_falls_back() {
+        let handler = started_handler();
+        let proposed = Move {
+            flag: 0,
+            card: card(mp::Color::Color3, 5),
+        };
+        assert!(!handler.state.is_legal_move(&proposed));
+        assert_eq!(Some(Move {
+                       flag: 0,
+                       card: card(mp::Color::Color1, 1),
+                   }),
+                   handler.state.legal_or_fallback(proposed));
+    }
+
+    #[test]
+    fn full_or_claimed_flag_falls_back() {
+        let mut handler = started_handler();
+        let ai = TestAi {};
+        handler.run_one_round(&ai, String::from("flag 1 cards north c,1 c,2 c,3")).unwrap();
+        handler.run_one_round(&ai,
+                              String::from("flag claim-status unclaimed north unclaimed \
+                                            unclaimed unclaimed unclaimed unclaimed unclaimed \
+                                            unclaimed"))
+            .unwrap();
+        let full = Move {
+            flag: 0,
+            card: card(mp::Color::Color1, 1),
+        };
+        let claimed = Move {
+            flag: 1,
+            card: card(mp::Color::Color1, 1),
+        };
+        assert!(!handler.state.is_legal_move(&full));
+        assert!(!handler.state.is_legal_move(&claimed));
+        assert_eq!(Some(Move {
+                       flag: 2,
+                       card: card(mp::Color::Color1, 1),
+                   }),
+                   handler.state.legal_or_fallback(full));
+    }
+
+    #[test]
+    fn move_to_command_uses_wire_flag_number() {
+        let handler = started_handler();
+        let chosen = Move {
+            flag: 8,
+            card: card(mp::Color::Color2, 5),
+        };
+        assert_eq!("play 9 b,5", chosen.to_command().to_wire(&handler.state));
+    }
+
+    #[test]
+    fn legal_moves_pair_hand_with_open_flags() {
+        let mut handler = started_handler();
+        let ai = TestAi {};
+        assert_eq!(18, handler.state.legal_moves().count());
+        handler.run_one_round(&ai, String::from("flag 1 cards north c,1 c,2 c,3")).unwrap();
+        handler.run_one_round(&ai,
+                              String::from("flag claim-status unclaimed unclaimed north \
+                                            unclaimed unclaimed unclaimed unclaimed unclaimed \
+                                            unclaimed"))
+            .unwrap();
+        let moves: Vec<Move> = handler.state.legal_moves().collect();
+        assert_eq!(14, moves.len());
+        assert!(moves.iter().all(|m| m.flag != 0 && m.flag != 2));
+        assert!(moves.iter().all(|m| handler.state.is_legal_move(m)));
+        assert_eq!(Move {
+                       flag: 1,
+                       card: card(mp::Color::Color1, 1),
+                   },
+                   moves[0]);
+        assert_eq!(Move {
+                       flag: 1,
+                       card: card(mp::Color::Color2, 5),
+                   },
+                   moves[7]);
+    }
+
+    #[test]
+    fn no_legal_moves_before_the_game_starts() {
+        let handler: GameHandler = Default::default();
+        assert_eq!(0, handler.state.legal_moves().count());
+    }
+
+    #[test]
+    fn prove_flag_needs_a_complete_side() {
+        let mut handler = started_handler();
+        let ai = TestAi {};
+        handler.run_one_round(&ai, String::from("flag 1 cards north c,1 c,2")).unwrap();
+        assert_eq!(FlagProof::Incomplete, handler.state.can_prove_flag(0));
+        assert_eq!(FlagProof::Incomplete, handler.state.can_prove_flag(12));
+    }
+
+    #[test]
+    fn prove_flag_when_opponent_cannot_catch_up() {
+        let mut handler = started_handler();
+        let ai = TestAi {};
+        handler.run_one_round(&ai, String::from("flag 1 cards north c,7 c,8 c,9")).unwrap();
+        handler.run_one_round(&ai, String::from("flag 1 cards south a,2 b,6")).unwrap();
+        match handler.state.can_prove_flag(0) {
+            FlagProof::Proven { player, opponent_best: Some(best) } => {
+                assert_eq!(formation::Formation::Wedge, player.formation);
+                assert_eq!(formation::Formation::Host, best.formation);
+            }
+            x => panic!("Unexpected proof {:?}", x),
+        }
+    }
+
+    #[test]
+    fn counter_example_when_opponent_can_win() {
+        let mut handler = started_handler();
+        let ai = TestAi {};
+        handler.run_one_round(&ai, String::from("flag 1 cards north c,7 d,8 e,9")).unwrap();
+        handler.run_one_round(&ai, String::from("flag 1 cards south a,2 a,3")).unwrap();
+        match handler.state.can_prove_flag(0) {
+            FlagProof::CounterExample { cards, strength } => {
+                assert_eq!(formation::Formation::Wedge, strength.formation);
+                assert_eq!(3, cards.len());
+                assert!(cards.contains(&card(mp::Color::Color1, 4)));
+            }
+            x => panic!("Unexpected proof {:?}", x),
+        }
+    }
+
+    #[test]
+    fn completed_opponent_side_is_compared_directly() {
+        let mut handler = started_handler();
+        let ai = TestAi {};
+        handler.run_one_round(&ai, String::from("flag 2 cards north c,7 d,8 e,9")).unwrap();
+        handler.run_one_round(&ai, String::from("flag 2 cards south a,7 b,8 f,9")).unwrap();
+        match handler.state.can_prove_flag(1) {
+            FlagProof::CounterExample { strength, .. } => {
+                assert_eq!(formation::Formation::SkirmishLine, strength.formation);
+            }
+            x => panic!("Unexpected proof {:?}", x),
+        }
+    }
+
+    #[test]
+    fn best_reachable_per_side() {
+        let mut handler = started_handler();
+        let ai = TestAi {};
+        handler.run_one_round(&ai, String::from("flag 1 cards north b,3 b,4")).unwrap();
+        handler.run_one_round(&ai, String::from("flag 1 cards south c,9 d,9")).unwrap();
+        let player = handler.state.player_best_reachable(0).unwrap();
+        assert_eq!(formation::Formation::Wedge, player.strength.formation);
+        assert_eq!(vec![card(mp::Color::Color2, 5)], player.needed);
+        let opponent = handler.state.opponent_best_reachable(0).unwrap();
+        assert_eq!(formation::Formation::Phalanx, opponent.strength.formation);
+        assert_eq!(27, opponent.strength.sum);
+    }
+
+    #[test]
+    fn replies_are_returned_and_written_to_output() {
+        let mut handler = GameHandler::with_output(vec![]);
+        let ai = TestAi {};
+        assert_eq!(Some(String::from("player north rusty_battleline_bot")),
+                   handler.run_one_round(&ai, String::from("player north name")).unwrap());
+        assert_eq!(None, handler.run_one_round(&ai, String::from("colors a b c d e f")).unwrap());
+        assert_eq!(None,
+                   handler.run_one_round(&ai, String::from("player north hand a,1 b,5")).unwrap());
+        assert_eq!(Some(String::from("play 1 a,1")),
+                   handler.run_one_round(&ai, String::from("go play-card")).unwrap());
+        assert_eq!("player north rusty_battleline_bot\nplay 1 a,1\n",
+                   String::from_utf8(handler.output).unwrap());
+    }
+
+    struct UnreachableAi {}
+
+    impl AiInterface for UnreachableAi {
+        fn choose_move(&self, _: &GameState) -> Option<Move> {
+            panic!("asked for a move with nothing legal to play");
+        }
+
+        fn get_bot_name(&self) -> String {
+            String::from("unreachable")
+        }
+    }
+
+    #[test]
+    fn no_reply_without_a_legal_move() {
+        let mut handler = GameHandler::with_output(io::sink());
+        let ai = UnreachableAi {};
+        assert_eq!(None, handler.respond(&ai, String::from("go play-card")));
+        handler.respond(&ai, String::from("player north name"));
+        handler.respond(&ai, String::from("colors a b c d e f"));
+        assert_eq!(None, handler.respond(&ai, String::from("go play-card")));
+    }
+
+    struct NoAnswerAi {}
+
+    impl AiInterface for NoAnswerAi {
+        fn choose_move(&self, _: &GameState) -> Option<Move> {
+            None
+        }
+
+        fn get_bot_name(&self) -> String {
+            String::from("no_answer")
+        }
+    }
+
+    #[test]
+    fn first_legal_move_is_played_without_an_answer() {
+        let mut handler = started_handler();
+        assert_eq!(Some(String::from("play 1 a,1")),
+                   handler.run_one_round(&NoAnswerAi {}, String::from("go play-card")).unwrap());
+    }
+
+    struct WireAi {}
+
+    impl AiInterface for WireAi {
+        fn update_game_state(&self, _: &GameState) -> String {
+            String::from("play 5 b,5")
+        }
+
+        fn get_bot_name(&self) -> String {
+            String::from("wire")
+        }
+    }
+
+    #[test]
+    fn string_answers_are_still_played() {
+        let mut handler = started_handler();
+        assert_eq!(Some(Move {
+                       flag: 4,
+                       card: card(mp::Color::Color2, 5),
+                   }),
+                   WireAi {}.choose_move(&handler.state));
+        assert_eq!(Some(String::from("play 5 b,5")),
+                   handler.run_one_round(&WireAi {}, String::from("go play-card")).unwrap());
+    }
+
+    #[test]
+    fn respond_returns_typed_commands() {
+        let mut handler = GameHandler::with_output(io::sink());
+        let ai = TestAi {};
+        assert_eq!(Some(command::Command::PlayerName {
+                       direction: mp::Direction::South,
+                       name: String::from("rusty_battleline_bot"),
+                   }),
+                   handler.respond(&ai, String::from("player south name")));
+        handler.respond(&ai, String::from("colors a b c d e f"));
+        handler.respond(&ai, String::from("player south hand c,2"));
+        assert_eq!(Some(command::Command::PlayCard {
+                       flag: 1,
+                       card: card(mp::Color::Color3, 2),
+                   }),
+                   handler.respond(&ai, String::from("go play-card")));
+    }
+
+    #[test]
+    fn opponent_plays_update_hand_probabilities() {
+        let mut handler = started_handler();
+        let ai = TestAi {};
+        let neighbour = card(mp::Color::Color4, 6);
+        let before = handler.state.opponent_hand_probability(&neighbour);
+        handler.run_one_round(&ai, String::from("opponent play 2 d,5")).unwrap();
+        assert_eq!(0.0, handler.state.opponent_hand_probability(&card(mp::Color::Color4, 5)));
+        assert!(handler.state.opponent_hand_probability(&neighbour) > before);
+        handler.run_one_round(&ai, String::from("flag 3 cards south e,9")).unwrap();
+        assert!(handler.state.opponent_hand_probability(&card(mp::Color::Color5, 8)) > before);
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test_serde {
+    use super::*;
+    use serde_json;
+    use test_support::FirstMoveBot;
+
+    #[test]
+    fn game_state_round_trips_through_json() {
+        let mut handler = GameHandler::with_output(io::sink());
+        let ai = FirstMoveBot;
+        handler.run_one_round(&ai, String::from("player south name")).unwrap();
+        handler.run_one_round(&ai, String::from("colors a b c d e f")).unwrap();
+        handler.run_one_round(&ai, String::from("player south hand a,7 c,3")).unwrap();
+        handler.run_one_round(&ai, String::from("flag 4 cards north b,2 b,3")).unwrap();
+        let json = serde_json::to_string(&handler.state).unwrap();
+        let loaded: GameState = serde_json::from_str(&json).unwrap();
+        assert_eq!(handler.state, loaded);
+        assert!(json.contains("\"player_direction\":\"South\""));
+    }
+
+    #[test]
+    fn messages_round_trip_through_json() {
+        let message = message_parsing::parse_message(String::from("opponent play 3 red,5"));
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(message, serde_json::from_str(&json).unwrap());
+    }
+
 }
@@ -0,0 +1,113 @@
+//! A one-move lookahead bot, meant to be easy to read and to beat.
+
+use evaluation::{Evaluator, FormationStrength};
+use game_state::{AiInterface, GameState, Move};
+
+/// Scores closer than this are a tie, so rounding does not decide between equal moves.
+const TIE: f64 = 1e-9;
+
+/// Plays the move leading to the position its evaluator likes best. By default that is
+/// `FormationStrength`: the move that leaves our side of its flag furthest ahead of the best
+/// the opponent can still reach there.
+pub struct GreedyBot {
+    pub evaluator: Box<dyn Evaluator>,
+}
+
+impl Default for GreedyBot {
+    fn default() -> GreedyBot {
+        GreedyBot::with_evaluator(Box::new(FormationStrength))
+    }
+}
+
+impl GreedyBot {
+    pub fn new() -> GreedyBot {
+        Default::default()
+    }
+    pub fn with_evaluator(evaluator: Box<dyn Evaluator>) -> GreedyBot {
+        GreedyBot { evaluator }
+    }
+
+    /// The evaluation of `state` once `proposed` has been played, before we draw.
+    pub fn score(&self, state: &GameState, proposed: &Move) -> f64 {
+        let mut after = state.clone();
+        after.player_side[proposed.flag].push(proposed.card);
+        after.player_hand.retain(|card| *card != proposed.card);
+        self.evaluator.evaluate(&after)
+    }
+}
+
+impl AiInterface for GreedyBot {
+    /// The highest scoring legal move, the first one in `legal_moves` order on a tie.
+    fn choose_move(&self, state: &GameState) -> Option<Move> {
+        let mut best: Option<(f64, Move)> = None;
+        for proposed in state.legal_moves() {
+            let score = self.score(state, &proposed);
+            if best.is_none_or(|(top, _)| score > top + TIE) {
+                best = Some((score, proposed));
+            }
+        }
+        best.map(|(_, chosen)| chosen)
+    }
+
+    fn get_bot_name(&self) -> String {
+        String::from("greedy")
+    }
+}
+
+#[cfg(test)]
+mod test_greedy {
+    use super::*;
+    use evaluation::Weighted;
+    use match_runner;
+    use message_parsing::Color;
+    use test_support::{card, FirstMoveBot};
+
+    #[test]
+    fn completes_a_winning_wedge() {
+        let state = GameState::from_notation("n red,orange,yellow,green,blue,purple \
+                                              |/c6c7|a1b3/|/|/|/|/|/|/| --------- a2c8d9")
+            .unwrap();
+        assert_eq!(Some(Move {
+                       flag: 1,
+                       card: card(Color::Color3, 8),
+                   }),
+                   GreedyBot::new().choose_move(&state));
+    }
+
+    #[test]
+    fn keeps_high_cards_off_lost_flags() {
+        // The opponent already holds a green wedge on the first flag.
+        let state = GameState::from_notation("n red,orange,yellow,green,blue,purple \
+                                              |d7d8d9/|/|/|/|/|/|/|/| --------- a1c9")
+            .unwrap();
+        let bot = GreedyBot::new();
+        let score = |flag, card| {
+            bot.score(&state,
+                      &Move {
+                          flag,
+                          card,
+                      })
+        };
+        assert!(score(0, card(Color::Color1, 1)) > score(0, card(Color::Color3, 9)));
+        assert!(score(1, card(Color::Color3, 9)) > score(0, card(Color::Color1, 1)));
+        assert_eq!(1, GreedyBot::new().choose_move(&state).unwrap().flag);
+    }
+
+    #[test]
+    fn evaluator_can_be_swapped() {
+        let state = GameState::from_notation("n red,orange,yellow,green,blue,purple \
+                                              |/c6c7|a1b3/|/|/|/|/|/|/| --------- a2c8d9")
+            .unwrap();
+        let indifferent = GreedyBot::with_evaluator(Box::new(Weighted::new()));
+        assert_eq!(state.legal_moves().next(), indifferent.choose_move(&state));
+        assert_eq!(None, GreedyBot::new().choose_move(&GameState::default()));
+    }
+
+    #[test]
+    fn beats_a_bot_that_plays_the_first_legal_move() {
+        for seed in 0..3 {
+            let result = match_runner::play_match(&GreedyBot::new(), &FirstMoveBot, seed);
+            assert_eq!(Some("greedy"), result.winner_name());
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use match_runner::MatchResult;
+use referee::{Referee, Seat, SeatError};
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{self, Child, ChildStdin, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// A seat backed by a bot executable: protocol lines go to its stdin and replies are read from
+/// its stdout, whatever language it is written in.
+///
+/// The process is killed when the seat is dropped.
+pub struct ProcessSeat {
+    child: Child,
+    stdin: ChildStdin,
+    replies: Receiver<String>,
+    timeout: Duration,
+}
+
+impl ProcessSeat {
+    /// Starts `command` with piped stdin and stdout. Every reply has to arrive within
+    /// `timeout`.
+    pub fn spawn(command: &mut process::Command, timeout: Duration) -> io::Result<ProcessSeat> {
+        let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, replies) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        Ok(ProcessSeat {
+            child,
+            stdin,
+            replies,
+            timeout,
+        })
+    }
+}
+
+impl Seat for ProcessSeat {
+    fn tell(&mut self, line: &str) -> Result<(), SeatError> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| SeatError::Disconnected)
+    }
+
+    fn ask(&mut self, line: &str) -> Result<String, SeatError> {
+        self.tell(line)?;
+        match self.replies.recv_timeout(self.timeout) {
+            Ok(reply) => Ok(reply),
+            Err(RecvTimeoutError::Timeout) => Err(SeatError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(SeatError::Disconnected),
+        }
+    }
+}
+
+impl Drop for ProcessSeat {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Plays one game between two bot executables with the deck shuffled from `seed`. A bot that
+/// crashes, times out or sends a malformed or illegal reply forfeits. Only failing to start a
+/// bot is an error.
+pub fn run_process_match(north: &mut process::Command,
+                         south: &mut process::Command,
+                         seed: u64,
+                         timeout: Duration)
+                         -> io::Result<MatchResult> {
+    let mut north = ProcessSeat::spawn(north, timeout)?;
+    let mut south = ProcessSeat::spawn(south, timeout)?;
+    let mut referee = Referee::shuffled(seed);
+    let result = referee.run(&mut north, &mut south);
+    Ok(MatchResult::from_game(&referee, result))
+}
+
+#[cfg(test)]
+mod test_harness {
+    use super::*;
+    use board::WinReason;
+    use message_parsing::Direction;
+
+    fn shell(script: &str) -> process::Command {
+        let mut command = process::Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    /// Answers the name request, then replies to every `go play-card` with `reply`.
+    fn scripted_bot(direction: &str, reply: &str) -> process::Command {
+        shell(&format!("read line; echo \"player {} shell_bot\"; while read line; do if [ \
+                        \"$line\" = \"go play-card\" ]; then echo \"{}\"; fi; done",
+                       direction,
+                       reply))
+    }
+
+    fn forfeit_reason(result: &MatchResult) -> &str {
+        match result.reason {
+            WinReason::Forfeit(ref reason) => reason,
+            ref reason => panic!("Unexpected reason {:?}", reason),
+        }
+    }
+
+    #[test]
+    fn seat_round_trips_lines() {
+        let mut seat = ProcessSeat::spawn(&mut shell("while read line; do echo \"got $line\"; \
+                                                      done"),
+                                          Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(Ok(()), seat.tell("hello"));
+        assert_eq!(Ok(String::from("got hello")), seat.replies.recv());
+        assert_eq!(Ok(String::from("got ping")), seat.ask("ping"));
+    }
+
+    #[test]
+    fn crashed_bot_forfeits() {
+        let result = run_process_match(&mut shell("exit 1"),
+                                       &mut scripted_bot("south", "play 1 red,1"),
+                                       1,
+                                       Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(Some(Direction::South), result.winner);
+        assert_eq!("north: disconnected", forfeit_reason(&result));
+    }
+
+    #[test]
+    fn silent_bot_times_out() {
+        let result = run_process_match(&mut scripted_bot("north", "play 1 red,1"),
+                                       &mut shell("sleep 10"),
+                                       1,
+                                       Duration::from_millis(200))
+            .unwrap();
+        assert_eq!(Some(Direction::North), result.winner);
+        assert_eq!("south: timed out", forfeit_reason(&result));
+    }
+
+    #[test]
+    fn malformed_reply_forfeits() {
+        let result = run_process_match(&mut scripted_bot("north", "resign"),
+                                       &mut scripted_bot("south", "resign"),
+                                       1,
+                                       Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(Some(Direction::South), result.winner);
+        assert!(forfeit_reason(&result).starts_with("north: malformed reply 'resign'"));
+        assert_eq!("shell_bot", result.north_name);
+    }
+
+    #[test]
+    fn missing_executable_is_an_error() {
+        assert!(run_process_match(&mut process::Command::new("/nonexistent/bot"),
+                                  &mut shell("exit 0"),
+                                  1,
+                                  Duration::from_secs(1))
+            .is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//! Guesses at the opponent's hand.
+//!
+//! Every unseen card starts out equally likely to be in the opponent's hand. When the
+//! opponent plays a card onto a flag that still has room, the unseen cards that would help
+//! that flag become more likely, since a player usually starts a formation they can finish.
+//! Each draw brings a fresh card into their hand, so old evidence fades by one card's share.
+
+use board::{HAND_SIZE, MAX_NUMBER};
+use game_state::GameState;
+use message_parsing::Card;
+use rng::{self, RandomSource};
+
+/// How much more likely a card becomes when it would complete the formation the opponent
+/// just started, from a wedge down to a plain skirmish line.
+const WEDGE_FIT: f64 = 3.0;
+const PHALANX_FIT: f64 = 2.5;
+const BATTALION_FIT: f64 = 1.5;
+const SKIRMISH_FIT: f64 = 1.25;
+
+/// Relative weights of the unseen cards being in the opponent's hand, and how many cards
+/// they hold. Kept up to date by `GameHandler` as plays and flag statuses arrive.
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OpponentModel {
+    weights: Vec<f64>,
+    pub hand_size: usize,
+}
+
+impl Default for OpponentModel {
+    fn default() -> OpponentModel {
+        OpponentModel {
+            weights: vec![1.0; 6 * MAX_NUMBER as usize],
+            hand_size: HAND_SIZE,
+        }
+    }
+}
+
+fn slot(card: &Card) -> Option<usize> {
+    if card.number < 1 || card.number > MAX_NUMBER {
+        return None;
+    }
+    Some(card.color.index() * MAX_NUMBER as usize + card.number as usize - 1)
+}
+
+/// How well `candidate` fits the opponent's side of a flag with room left.
+fn fit(side: &[Card], candidate: &Card) -> f64 {
+    let mut cards = side.to_vec();
+    cards.push(*candidate);
+    let mut numbers: Vec<i32> = cards.iter().map(|card| card.number).collect();
+    numbers.sort();
+    let same_color = cards.iter().all(|card| card.color == candidate.color);
+    let distinct = numbers.windows(2).all(|pair| pair[0] != pair[1]);
+    let run = distinct && numbers[numbers.len() - 1] - numbers[0] <= 2;
+    if same_color && run {
+        WEDGE_FIT
+    } else if numbers.iter().all(|number| *number == candidate.number) {
+        PHALANX_FIT
+    } else if same_color {
+        BATTALION_FIT
+    } else if run {
+        SKIRMISH_FIT
+    } else {
+        1.0
+    }
+}
+
+impl OpponentModel {
+    fn weight(&self, card: &Card) -> f64 {
+        slot(card).and_then(|index| self.weights.get(index)).cloned().unwrap_or(1.0)
+    }
+
+    /// Updates the model after the opponent played a card onto a flag whose side is now
+    /// `side`. `unseen` is what we have not seen yet, without the played card.
+    pub fn observe_play(&mut self, side: &[Card], unseen: &[Card]) {
+        if side.len() < 3 {
+            for candidate in unseen {
+                if let Some(index) = slot(candidate) {
+                    self.weights[index] *= fit(side, candidate);
+                }
+            }
+        }
+        if unseen.len() > self.hand_size {
+            let keep = (self.hand_size as f64 - 1.0) / self.hand_size as f64;
+            for weight in &mut self.weights {
+                *weight = 1.0 + (*weight - 1.0) * keep;
+            }
+        } else {
+            self.hand_size = self.hand_size.saturating_sub(1);
+        }
+    }
+
+    /// Chance that `card` is in the opponent's hand, given the cards in `unseen`. Summed over
+    /// `unseen` this comes to `hand_size`, apart from the cap at one. Once the draw pile is
+    /// gone every unseen card is in their hand.
+    pub fn probability(&self, card: &Card, unseen: &[Card]) -> f64 {
+        if self.hand_size == 0 || !unseen.contains(card) {
+            return 0.0;
+        }
+        if unseen.len() <= self.hand_size {
+            return 1.0;
+        }
+        let total: f64 = unseen.iter().map(|other| self.weight(other)).sum();
+        (self.hand_size as f64 * self.weight(card) / total).min(1.0)
+    }
+}
+
+impl GameState {
+    /// Chance that the opponent holds `card`, from what they have played so far.
+    pub fn opponent_hand_probability(&self, card: &Card) -> f64 {
+        self.opponent_model.probability(card, &self.deck)
+    }
+    /// Splits `deck` into a guess at the opponent's hand, drawn by
+    /// `opponent_hand_probability`, and the shuffled rest.
+    pub fn sample_opponent_hand(&self, rng: &mut dyn RandomSource) -> (Vec<Card>, Vec<Card>) {
+        let mut unseen = self.deck.clone();
+        let mut weights: Vec<f64> =
+            unseen.iter().map(|card| self.opponent_hand_probability(card)).collect();
+        let mut hand = vec![];
+        for _ in 0..self.opponent_model.hand_size.min(unseen.len()) {
+            let total: f64 = weights.iter().sum();
+            let mut target = rng.next_f64() * total;
+            let mut index = 0;
+            while index + 1 < weights.len() && target >= weights[index] {
+                target -= weights[index];
+                index += 1;
+            }
+            weights.swap_remove(index);
+            hand.push(unseen.swap_remove(index));
+        }
+        rng::shuffle(rng, &mut unseen);
+        (hand, unseen)
+    }
+}
+
+#[cfg(test)]
+mod test_inference {
+    use super::*;
+    use board;
+    use message_parsing::Color;
+    use rng::SeededRng;
+    use test_support::card;
+
+    fn without(cards: &[Card], removed: &Card) -> Vec<Card> {
+        cards.iter().filter(|card| *card != removed).cloned().collect()
+    }
+
+    #[test]
+    fn starts_uniform() {
+        let model = OpponentModel::default();
+        let unseen = board::full_deck();
+        let p = model.probability(&card(Color::Color3, 5), &unseen);
+        assert!((p - 7.0 / 54.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn seen_cards_have_no_chance() {
+        let model = OpponentModel::default();
+        let played = card(Color::Color1, 1);
+        let unseen = without(&board::full_deck(), &played);
+        assert_eq!(0.0, model.probability(&played, &unseen));
+    }
+
+    #[test]
+    fn play_raises_cards_that_fit_the_flag() {
+        let mut model = OpponentModel::default();
+        let played = card(Color::Color2, 5);
+        let unseen = without(&board::full_deck(), &played);
+        let before = model.probability(&card(Color::Color2, 6), &unseen);
+        model.observe_play(&[played], &unseen);
+        let neighbour = model.probability(&card(Color::Color2, 6), &unseen);
+        let same_number = model.probability(&card(Color::Color4, 5), &unseen);
+        let unrelated = model.probability(&card(Color::Color5, 1), &unseen);
+        assert!(neighbour > before);
+        assert!(neighbour > same_number);
+        assert!(same_number > unrelated);
+        let total: f64 = unseen.iter().map(|other| model.probability(other, &unseen)).sum();
+        assert!((total - 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn completed_flag_tells_nothing() {
+        let mut model = OpponentModel::default();
+        let side = [card(Color::Color1, 1), card(Color::Color1, 2), card(Color::Color1, 3)];
+        let unseen: Vec<Card> =
+            board::full_deck().into_iter().filter(|card| !side.contains(card)).collect();
+        model.observe_play(&side, &unseen);
+        assert_eq!(OpponentModel::default(), model);
+    }
+
+    #[test]
+    fn sampled_hands_split_the_deck() {
+        let mut state = GameState::from_notation("n red,orange,yellow,green,blue,purple \
+                                                  |a5/|/|/|/|/|/|/|/| --------- -")
+            .unwrap();
+        state.opponent_model.observe_play(&[card(Color::Color1, 5)], &state.deck);
+        let mut rng = SeededRng::new(6);
+        let mut neighbours = 0;
+        for _ in 0..200 {
+            let (hand, rest) = state.sample_opponent_hand(&mut rng);
+            assert_eq!(7, hand.len());
+            assert_eq!(state.deck.len(), hand.len() + rest.len());
+            assert!(hand.iter().all(|card| !rest.contains(card) && state.deck.contains(card)));
+            if hand.contains(&card(Color::Color1, 6)) {
+                neighbours += 1;
+            }
+        }
+        // Uniform draws would hold it 7 times in 53, about 26 of the 200 hands.
+        assert!(neighbours > 40);
+    }
+
+    #[test]
+    fn hand_shrinks_once_the_pile_is_empty() {
+        let mut model = OpponentModel::default();
+        let unseen: Vec<Card> = board::full_deck().into_iter().take(7).collect();
+        model.observe_play(&[unseen[0]], &unseen[1..]);
+        assert_eq!(6, model.hand_size);
+        for other in &unseen[1..] {
+            assert_eq!(1.0, model.probability(other, &unseen[1..]));
+        }
+    }
+}
@@ -1,4 +1,28 @@
-#![feature(advanced_slice_patterns, slice_patterns)]
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 pub mod message_parsing;
 pub mod game_state;
+pub mod command;
+pub mod formation;
+pub mod driver;
+pub mod board;
+pub mod referee;
+pub mod match_runner;
+pub mod harness;
+pub mod tournament;
+pub mod rng;
+pub mod transcript;
+pub mod notation;
+pub mod render;
+pub mod inference;
+pub mod mcts;
+pub mod greedy;
+pub mod random_bot;
+pub mod search;
+pub mod evaluation;
+#[cfg(test)]
+mod test_support;
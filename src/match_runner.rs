@@ -0,0 +1,177 @@
+use board::WinReason;
+use game_state::{AiInterface, GameHandler};
+use message_parsing::{ClaimStatus, Direction};
+use referee::{GameResult, PlayedMove, Referee, Seat, SeatError};
+use std::io;
+
+/// A seat backed by a `GameHandler` and an AI in the same process.
+pub struct HandlerSeat<'a> {
+    pub handler: GameHandler<io::Sink>,
+    ai: &'a dyn AiInterface,
+}
+
+impl<'a> HandlerSeat<'a> {
+    pub fn new(ai: &'a dyn AiInterface) -> HandlerSeat<'a> {
+        HandlerSeat {
+            handler: GameHandler::with_output(io::sink()),
+            ai,
+        }
+    }
+}
+
+impl<'a> Seat for HandlerSeat<'a> {
+    fn tell(&mut self, line: &str) -> Result<(), SeatError> {
+        self.handler.respond(self.ai, String::from(line));
+        Ok(())
+    }
+
+    fn ask(&mut self, line: &str) -> Result<String, SeatError> {
+        let reply = self.handler.respond(self.ai, String::from(line));
+        reply.map(|reply| reply.to_wire(&self.handler.state)).ok_or(SeatError::Disconnected)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct MatchResult {
+    /// `None` for a draw.
+    pub winner: Option<Direction>,
+    pub reason: WinReason,
+    pub north_name: String,
+    pub south_name: String,
+    /// Who held each flag when the game ended.
+    pub flags: Vec<ClaimStatus>,
+    pub moves: Vec<PlayedMove>,
+    /// The seed the deck was shuffled from. Playing the same bots again with it reproduces the
+    /// game line for line.
+    pub seed: Option<u64>,
+}
+
+impl MatchResult {
+    /// Collects the outcome of a game `referee` has finished running.
+    pub fn from_game(referee: &Referee, result: GameResult) -> MatchResult {
+        MatchResult {
+            winner: result.winner,
+            reason: result.reason,
+            north_name: String::from(referee.name(Direction::North)),
+            south_name: String::from(referee.name(Direction::South)),
+            flags: result.board.claims,
+            moves: result.moves,
+            seed: referee.seed(),
+        }
+    }
+    /// The winner's name, or `None` for a draw.
+    pub fn winner_name(&self) -> Option<&str> {
+        self.winner.map(|winner| {
+            match winner {
+                Direction::North => &self.north_name[..],
+                Direction::South => &self.south_name[..],
+            }
+        })
+    }
+}
+
+/// Plays a whole game between two AIs in memory, each behind its own `GameHandler` fed the
+/// referee's protocol lines, with the deck shuffled from `seed`.
+pub fn play_match(north: &dyn AiInterface, south: &dyn AiInterface, seed: u64) -> MatchResult {
+    let mut referee = Referee::shuffled(seed);
+    let result = referee.run(&mut HandlerSeat::new(north), &mut HandlerSeat::new(south));
+    MatchResult::from_game(&referee, result)
+}
+
+#[cfg(test)]
+mod test_match_runner {
+    use super::*;
+    use game_state::{GameState, Move};
+    use test_support::FirstMoveBot;
+
+    struct LastMoveAi {}
+
+    impl AiInterface for LastMoveAi {
+        fn choose_move(&self, state: &GameState) -> Option<Move> {
+            state.legal_moves().last()
+        }
+
+        fn get_bot_name(&self) -> String {
+            String::from("last")
+        }
+    }
+
+    #[test]
+    fn match_is_played_to_a_decision() {
+        let result = play_match(&FirstMoveBot, &LastMoveAi {}, 17);
+        assert_eq!("first", result.north_name);
+        assert_eq!("last", result.south_name);
+        assert_eq!(9, result.flags.len());
+        match result.reason {
+            WinReason::Envelopment | WinReason::Breakthrough => {}
+            ref reason => panic!("Unexpected reason {:?}", reason),
+        }
+        let won = result.flags
+            .iter()
+            .filter(|claim| match result.winner {
+                Some(Direction::North) => **claim == ClaimStatus::North,
+                Some(Direction::South) => **claim == ClaimStatus::South,
+                None => false,
+            })
+            .count();
+        assert!(won >= 3);
+    }
+
+    #[test]
+    fn same_seed_replays_the_same_match() {
+        for seed in 0..20 {
+            assert_eq!(play_match(&FirstMoveBot, &LastMoveAi {}, seed),
+                       play_match(&FirstMoveBot, &LastMoveAi {}, seed));
+        }
+    }
+
+    #[test]
+    fn north_moves_first() {
+        let result = play_match(&LastMoveAi {}, &LastMoveAi {}, 3);
+        assert_eq!(Direction::North, result.moves[0].direction);
+        assert!(result.moves.len() > 10);
+        assert_eq!(Some("last"), result.winner_name());
+    }
+
+    /// Passes lines through to a `HandlerSeat`, keeping a copy of everything sent and received.
+    struct RecordingSeat<'a> {
+        seat: HandlerSeat<'a>,
+        lines: Vec<String>,
+    }
+
+    impl<'a> Seat for RecordingSeat<'a> {
+        fn tell(&mut self, line: &str) -> Result<(), SeatError> {
+            self.lines.push(String::from(line));
+            self.seat.tell(line)
+        }
+
+        fn ask(&mut self, line: &str) -> Result<String, SeatError> {
+            self.lines.push(String::from(line));
+            let reply = self.seat.ask(line)?;
+            self.lines.push(reply.clone());
+            Ok(reply)
+        }
+    }
+
+    fn record_game(seed: u64) -> (Vec<String>, Vec<String>) {
+        let (first, last) = (FirstMoveBot, LastMoveAi {});
+        let mut north = RecordingSeat {
+            seat: HandlerSeat::new(&first),
+            lines: vec![],
+        };
+        let mut south = RecordingSeat {
+            seat: HandlerSeat::new(&last),
+            lines: vec![],
+        };
+        Referee::shuffled(seed).run(&mut north, &mut south);
+        (north.lines, south.lines)
+    }
+
+    #[test]
+    fn seed_is_recorded_and_replays_byte_for_byte() {
+        let result = play_match(&FirstMoveBot, &LastMoveAi {}, 99);
+        assert_eq!(Some(99), result.seed);
+        assert_eq!(record_game(99), record_game(99));
+        assert!(record_game(99) != record_game(100));
+    }
+}
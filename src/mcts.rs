@@ -0,0 +1,330 @@
+//! A Monte Carlo tree search bot.
+//!
+//! The opponent's hand is hidden, so this is information set MCTS: every iteration deals the
+//! unseen cards into a guessed opponent hand and a draw pile, walks one shared tree using only
+//! the moves possible in that deal, plays the rest of the game out at random and counts the
+//! result along the path. The move tried most often at the root is played.
+
+use board::{self, Board};
+use game_state::{AiInterface, GameState, Move};
+use message_parsing::{Card, Direction};
+use rng::{RandomSource, SeededRng};
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct MctsConfig {
+    /// Iterations to run for each move.
+    pub iterations: usize,
+    /// Stops a move's search early once this much time has passed.
+    pub time_budget: Option<Duration>,
+    /// Weight of the exploration term in the UCB1 formula.
+    pub exploration: f64,
+    pub seed: u64,
+}
+
+impl Default for MctsConfig {
+    fn default() -> MctsConfig {
+        MctsConfig {
+            iterations: 1000,
+            time_budget: None,
+            exploration: 0.7,
+            seed: 0,
+        }
+    }
+}
+
+/// The rest of a game under one guess of the hidden cards.
+#[derive(Clone)]
+struct Playout {
+    board: Board,
+    /// Indexed by `Direction as usize`.
+    hands: [Vec<Card>; 2],
+    pile: Vec<Card>,
+    to_move: Direction,
+}
+
+impl Playout {
+    /// Deals the unseen cards of `state`, picking the opponent's hand by
+    /// `GameState::opponent_hand_probability`.
+    fn deal(state: &GameState, rng: &mut dyn RandomSource) -> Playout {
+        let (theirs, pile) = state.sample_opponent_hand(rng);
+        let mut hands = [vec![], vec![]];
+        hands[state.player_direction as usize] = state.player_hand.clone();
+        hands[state.opponent_direction as usize] = theirs;
+        let mut playout = Playout {
+            board: Board::from_state(state),
+            hands,
+            pile,
+            to_move: state.player_direction,
+        };
+        let unplayed = playout.unplayed();
+        playout.board.resolve_flags(&unplayed);
+        playout
+    }
+
+    fn unplayed(&self) -> Vec<Card> {
+        self.pile.iter().chain(self.hands[0].iter()).chain(self.hands[1].iter()).cloned().collect()
+    }
+
+    fn moves(&self) -> Vec<Move> {
+        let flags = self.board.open_flags(self.to_move);
+        self.hands[self.to_move as usize]
+            .iter()
+            .flat_map(|card| {
+                flags.iter().map(move |flag| {
+                    Move {
+                        flag: *flag,
+                        card: *card,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Passes the turn while the player to move is stuck, and once the game is over returns
+    /// its winner, `None` for a draw, by the same rules as `Referee::run`.
+    fn settle(&mut self) -> Option<Option<Direction>> {
+        let mut passes = 0;
+        loop {
+            if let Some((winner, _)) = self.board.game_over(|| passes == 2) {
+                return Some(winner);
+            }
+            if !self.moves().is_empty() {
+                return None;
+            }
+            passes += 1;
+            self.to_move = board::other_direction(self.to_move);
+        }
+    }
+
+    fn play(&mut self, chosen: Move) {
+        let hand = &mut self.hands[self.to_move as usize];
+        if let Some(index) = hand.iter().position(|card| *card == chosen.card) {
+            hand.remove(index);
+        }
+        if !self.pile.is_empty() {
+            hand.push(self.pile.remove(0));
+        }
+        self.board.play(self.to_move, chosen.flag, chosen.card);
+        let unplayed = self.unplayed();
+        self.board.resolve_flags(&unplayed);
+        self.to_move = board::other_direction(self.to_move);
+    }
+
+    /// Plays uniformly random moves until the game ends, and returns its winner.
+    fn finish(&mut self, rng: &mut dyn RandomSource) -> Option<Direction> {
+        loop {
+            if let Some(winner) = self.settle() {
+                return winner;
+            }
+            let moves = self.moves();
+            self.play(moves[rng.below(moves.len())]);
+        }
+    }
+}
+
+struct Node {
+    /// The move leading here and who made it. `None` only for the root.
+    edge: Option<(Direction, Move)>,
+    visits: u32,
+    /// Iterations in which this node's move could have been played.
+    available: u32,
+    wins: f64,
+    children: Vec<usize>,
+}
+
+impl Node {
+    fn new(edge: Option<(Direction, Move)>) -> Node {
+        Node {
+            edge,
+            visits: 0,
+            available: 0,
+            wins: 0.0,
+            children: vec![],
+        }
+    }
+}
+
+pub struct MctsBot {
+    pub config: MctsConfig,
+    rng: RefCell<SeededRng>,
+}
+
+impl MctsBot {
+    pub fn new(config: MctsConfig) -> MctsBot {
+        MctsBot {
+            rng: RefCell::new(SeededRng::new(config.seed)),
+            config,
+        }
+    }
+
+    /// Runs the search from `state` and returns the most visited move.
+    ///
+    /// # Panics
+    ///
+    /// When `state` has no legal move.
+    pub fn search(&self, state: &GameState) -> Move {
+        let legal: Vec<Move> = state.legal_moves().collect();
+        assert!(!legal.is_empty(), "no legal move to search");
+        if legal.len() == 1 {
+            return legal[0];
+        }
+        let mut rng = self.rng.borrow_mut();
+        let mut tree = vec![Node::new(None)];
+        let started = Instant::now();
+        for iteration in 0..self.config.iterations {
+            if iteration > 0 && self.config.time_budget.is_some_and(|b| started.elapsed() >= b) {
+                break;
+            }
+            self.iterate(&mut tree, Playout::deal(state, &mut *rng), &mut *rng);
+        }
+        tree[0]
+            .children
+            .iter()
+            .max_by_key(|child| tree[**child].visits)
+            .and_then(|child| tree[*child].edge)
+            .map_or(legal[0], |(_, chosen)| chosen)
+    }
+
+    fn iterate(&self, tree: &mut Vec<Node>, mut playout: Playout, rng: &mut dyn RandomSource) {
+        let mut path = vec![0];
+        let mut node = 0;
+        while playout.settle().is_none() {
+            let mover = playout.to_move;
+            let moves = playout.moves();
+            let mut untried = moves.clone();
+            let mut best: Option<(f64, usize)> = None;
+            for child in tree[node].children.clone() {
+                let (direction, tried) = match tree[child].edge {
+                    Some(edge) => edge,
+                    None => continue,
+                };
+                if direction != mover || !moves.contains(&tried) {
+                    continue;
+                }
+                untried.retain(|m| *m != tried);
+                tree[child].available += 1;
+                let score = self.ucb(&tree[child]);
+                if best.is_none_or(|(top, _)| score > top) {
+                    best = Some((score, child));
+                }
+            }
+            if !untried.is_empty() {
+                let chosen = untried[rng.below(untried.len())];
+                tree.push(Node::new(Some((mover, chosen))));
+                let child = tree.len() - 1;
+                tree[child].available = 1;
+                tree[node].children.push(child);
+                path.push(child);
+                playout.play(chosen);
+                break;
+            }
+            node = match best {
+                Some((_, child)) => child,
+                None => break,
+            };
+            path.push(node);
+            if let Some((_, chosen)) = tree[node].edge {
+                playout.play(chosen);
+            }
+        }
+        let winner = playout.finish(rng);
+        for index in path {
+            let node = &mut tree[index];
+            node.visits += 1;
+            if let Some((mover, _)) = node.edge {
+                node.wins += match winner {
+                    Some(winner) if winner == mover => 1.0,
+                    Some(_) => 0.0,
+                    None => 0.5,
+                };
+            }
+        }
+    }
+
+    fn ucb(&self, node: &Node) -> f64 {
+        if node.visits == 0 {
+            return f64::INFINITY;
+        }
+        let visits = node.visits as f64;
+        node.wins / visits +
+        self.config.exploration * ((node.available as f64).ln() / visits).sqrt()
+    }
+}
+
+impl AiInterface for MctsBot {
+    fn choose_move(&self, state: &GameState) -> Option<Move> {
+        state.legal_moves().next().map(|_| self.search(state))
+    }
+
+    fn get_bot_name(&self) -> String {
+        String::from("mcts")
+    }
+}
+
+#[cfg(test)]
+mod test_mcts {
+    use super::*;
+    use match_runner;
+    use message_parsing::Color;
+    use test_support::FirstMoveBot;
+
+    fn config(iterations: usize, seed: u64) -> MctsConfig {
+        MctsConfig {
+            iterations,
+            seed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn takes_the_winning_flag() {
+        // We hold flags 1 and 2; a purple 9 makes a wedge on flag 3 for the breakthrough.
+        let state = GameState::from_notation("n red,orange,yellow,green,blue,purple \
+                                              a1a2a3|b1c2d4/b4b5b6|c7d8e9/f7f8|a9b9c9/|/|/\
+                                              |/|/|/| nn------- f9d1")
+            .unwrap();
+        let bot = MctsBot::new(config(300, 5));
+        assert_eq!(Move {
+                       flag: 2,
+                       card: Card {
+                           color: Color::Color6,
+                           number: 9,
+                       },
+                   },
+                   bot.search(&state));
+    }
+
+    #[test]
+    fn same_seed_same_move() {
+        let state = GameState::from_notation("s red,orange,yellow,green,blue,purple \
+                                              a5|/|/|b2/|/|/|/|/|/| --------- a6c3d9e1f4")
+            .unwrap();
+        let first = MctsBot::new(config(60, 9)).search(&state);
+        assert_eq!(first, MctsBot::new(config(60, 9)).search(&state));
+        assert!(state.is_legal_move(&first));
+    }
+
+    #[test]
+    fn time_budget_cuts_the_search_short() {
+        let state = GameState::from_notation("n red,orange,yellow,green,blue,purple \
+                                              |/|/|/|/|/|/|/|/| --------- a1b2c3d4e5f6a7")
+            .unwrap();
+        let bot = MctsBot::new(MctsConfig {
+            iterations: usize::MAX,
+            time_budget: Some(Duration::from_millis(50)),
+            ..Default::default()
+        });
+        let started = Instant::now();
+        assert!(state.is_legal_move(&bot.search(&state)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn beats_a_bot_that_plays_the_first_legal_move() {
+        let bot = MctsBot::new(config(40, 2));
+        let result = match_runner::play_match(&bot, &FirstMoveBot, 3);
+        assert_eq!(Some("mcts"), result.winner_name());
+    }
+}
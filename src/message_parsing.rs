@@ -1,21 +1,22 @@
-#[derive(Debug, PartialEq, Copy, Clone)]
+use std::error;
+use std::fmt;
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
 pub enum Direction {
+    #[default]
     North,
     South,
 }
 
-impl Default for Direction {
-    fn default() -> Direction {
-        Direction::North
-    }
-}
-
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(PartialEq, Debug, Clone)]
 pub struct CardString {
     pub color: String,
     pub number: i32,
 }
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub enum Color {
     Color1,
@@ -26,26 +27,49 @@ pub enum Color {
     Color6,
 }
 
+impl Color {
+    /// The color announced at `index` in the `colors` message.
+    pub fn from_index(index: usize) -> Option<Color> {
+        match index {
+            0 => Some(Color::Color1),
+            1 => Some(Color::Color2),
+            2 => Some(Color::Color3),
+            3 => Some(Color::Color4),
+            4 => Some(Color::Color5),
+            5 => Some(Color::Color6),
+            _ => None,
+        }
+    }
+    pub fn index(self) -> usize {
+        match self {
+            Color::Color1 => 0,
+            Color::Color2 => 1,
+            Color::Color3 => 2,
+            Color::Color4 => 3,
+            Color::Color5 => 4,
+            Color::Color6 => 5,
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub struct Card {
     pub color: Color,
     pub number: i32,
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Debug, Clone, Default)]
 pub enum ClaimStatus {
+    #[default]
     Unclaimed,
     North,
     South,
 }
 
-impl Default for ClaimStatus {
-    fn default() -> ClaimStatus {
-        ClaimStatus::Unclaimed
-    }
-}
-
-// #[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Debug, Clone)]
 pub enum Message {
     Blank,
     PlayerDirection {
@@ -73,11 +97,152 @@ pub enum Message {
     PlayCard,
 }
 
-fn convert_direction(message: &str) -> Direction {
+/// Why a line from the server could not be turned into a `Message`.
+///
+/// Token positions are zero based indexes into the whitespace separated line.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ParseError {
+    /// The line had no tokens at all.
+    Empty,
+    /// The first token does not start any message the server sends.
+    UnknownMessage { token: String },
+    /// A token did not match the grammar expected at its position.
+    InvalidToken {
+        token: String,
+        position: usize,
+        expected: &'static str,
+    },
+    /// The line had the wrong number of tokens for its message.
+    WrongTokenCount {
+        found: usize,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Empty => write!(f, "empty message"),
+            ParseError::UnknownMessage { ref token } => write!(f, "unknown message '{}'", token),
+            ParseError::InvalidToken { ref token, position, expected } => {
+                write!(f,
+                       "invalid token '{}' at position {}, expected {}",
+                       token,
+                       position,
+                       expected)
+            }
+            ParseError::WrongTokenCount { found, expected } => {
+                write!(f, "found {} tokens, expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&get_direction_string(*self))
+    }
+}
+
+impl fmt::Display for ClaimStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClaimStatus::Unclaimed => f.write_str("unclaimed"),
+            ClaimStatus::North => f.write_str("north"),
+            ClaimStatus::South => f.write_str("south"),
+        }
+    }
+}
+
+impl fmt::Display for CardString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.color, self.number)
+    }
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter, items: &[T]) -> fmt::Result {
+    for item in items {
+        write!(f, " {}", item)?;
+    }
+    Ok(())
+}
+
+/// Formats the message as the exact line the server sends, so that `parse_message` gives the
+/// same message back. `Blank` formats as an empty line.
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Message::Blank => Ok(()),
+            Message::PlayerDirection { direction } => write!(f, "player {} name", direction),
+            Message::ColorNames { ref colors } => {
+                f.write_str("colors")?;
+                write_list(f, colors)
+            }
+            Message::FlagClaimStatus { ref flags_claimed } => {
+                f.write_str("flag claim-status")?;
+                write_list(f, flags_claimed)
+            }
+            Message::FlagStatus { flag_num, direction, ref cards } => {
+                write!(f, "flag {} cards {}", flag_num, direction)?;
+                write_list(f, cards)
+            }
+            Message::OpponentPlay { number, ref card } => {
+                write!(f, "opponent play {} {}", number, card)
+            }
+            Message::PlayerHand { direction, ref cards } => {
+                write!(f, "player {} hand", direction)?;
+                write_list(f, cards)
+            }
+            Message::PlayCard => f.write_str("go play-card"),
+        }
+    }
+}
+
+const DIRECTION_GRAMMAR: &str = "north|south";
+const CLAIM_STATUS_GRAMMAR: &str = "north|south|unclaimed";
+const CARD_GRAMMAR: &str = "<color>,<number>";
+const FLAG_GRAMMAR: &str = "<flag 1-9>";
+
+pub(crate) fn invalid(token: &str, position: usize, expected: &'static str) -> ParseError {
+    ParseError::InvalidToken {
+        token: String::from(token),
+        position,
+        expected,
+    }
+}
+
+pub(crate) fn expect_token(split: &[&str],
+                           position: usize,
+                           token: &'static str)
+                           -> Result<(), ParseError> {
+    if split[position] == token {
+        Ok(())
+    } else {
+        Err(invalid(split[position], position, token))
+    }
+}
+
+pub(crate) fn expect_count(split: &[&str],
+                           count: usize,
+                           expected: &'static str)
+                           -> Result<(), ParseError> {
+    if split.len() == count {
+        Ok(())
+    } else {
+        Err(ParseError::WrongTokenCount {
+            found: split.len(),
+            expected,
+        })
+    }
+}
+
+pub(crate) fn convert_direction(message: &str, position: usize) -> Result<Direction, ParseError> {
     match message {
-        "north" => Direction::North,
-        "south" => Direction::South,
-        e => panic!("{} is not a direction flag.", e),
+        "north" => Ok(Direction::North),
+        "south" => Ok(Direction::South),
+        e => Err(invalid(e, position, DIRECTION_GRAMMAR)),
     }
 }
 
@@ -89,86 +254,146 @@ pub fn get_direction_string(direction: Direction) -> String {
 }
 
 
-fn convert_claim_status(message: &str) -> ClaimStatus {
+fn convert_claim_status(message: &str, position: usize) -> Result<ClaimStatus, ParseError> {
     match message {
-        "north" => ClaimStatus::North,
-        "south" => ClaimStatus::South,
-        "unclaimed" => ClaimStatus::Unclaimed,
-        e => panic!("{} is not a Claim Status flag.", e),
+        "north" => Ok(ClaimStatus::North),
+        "south" => Ok(ClaimStatus::South),
+        "unclaimed" => Ok(ClaimStatus::Unclaimed),
+        e => Err(invalid(e, position, CLAIM_STATUS_GRAMMAR)),
     }
 }
 
-fn convert_string_to_card(message: &str) -> CardString {
-    let split: Vec<&str> = message.split(',').collect();
-    let b = String::from(split[0]);
-    let c = split[1].parse::<i32>().unwrap();
-    CardString {
-        color: b,
-        number: c,
+pub(crate) fn convert_flag_number(message: &str, position: usize) -> Result<u32, ParseError> {
+    match message.parse::<u32>() {
+        Ok(flag) if (1..=9).contains(&flag) => Ok(flag),
+        _ => Err(invalid(message, position, FLAG_GRAMMAR)),
     }
 }
 
-pub fn parse_message(message: String) -> Message {
-    let split: Vec<&str> = message.split_whitespace().collect();
-    match &split[..] {
-        &["go", "play-card"] => Message::PlayCard,
-        &["player", direction, "hand", ref cards..] if cards.len() <= 7 &&
-                                                       (direction == "north" ||
-                                                        direction == "south") => {
-            let mut player_cards = Vec::new();
-            for i in cards {
-                player_cards.push(convert_string_to_card(i));
-            }
-            Message::PlayerHand {
-                direction: convert_direction(direction),
-                cards: player_cards,
+pub(crate) fn convert_string_to_card(message: &str,
+                                     position: usize)
+                                     -> Result<CardString, ParseError> {
+    let mut split = message.split(',');
+    match (split.next(), split.next(), split.next()) {
+        (Some(color), Some(number), None) if !color.is_empty() => {
+            match number.parse::<i32>() {
+                Ok(number) => {
+                    Ok(CardString {
+                        color: String::from(color),
+                        number,
+                    })
+                }
+                Err(_) => Err(invalid(message, position, CARD_GRAMMAR)),
             }
         }
-        &["flag", flag, "cards", direction, ref cards..] if flag >= "1" && flag <= "9" &&
-                                                            (direction == "north" ||
-                                                             direction == "south") => {
-            let mut flags_cards = Vec::new();
-            let a = flag.parse::<u32>().unwrap();
-            for i in cards {
-                flags_cards.push(convert_string_to_card(i));
-            }
-            Message::FlagStatus {
-                flag_num: a,
-                direction: convert_direction(direction),
-                cards: flags_cards,
-            }
-        }
-        &["player", direction, "name"] if direction == "north" || direction == "south" => {
-            match direction {
-                "north" => Message::PlayerDirection { direction: Direction::North },
-                _ => Message::PlayerDirection { direction: Direction::South },
-            }
+        _ => Err(invalid(message, position, CARD_GRAMMAR)),
+    }
+}
+
+fn convert_cards(cards: &[&str], first_position: usize) -> Result<Vec<CardString>, ParseError> {
+    cards.iter()
+        .enumerate()
+        .map(|(i, card)| convert_string_to_card(card, first_position + i))
+        .collect()
+}
+
+fn parse_player_message(split: &[&str]) -> Result<Message, ParseError> {
+    if split.len() < 3 {
+        return Err(ParseError::WrongTokenCount {
+            found: split.len(),
+            expected: "player <direction> name|hand ...",
+        });
+    }
+    let direction = convert_direction(split[1], 1)?;
+    match split[2] {
+        "name" => {
+            expect_count(split, 3, "player <direction> name")?;
+            Ok(Message::PlayerDirection { direction })
         }
-        &["opponent", "play", flag, card] if flag >= "1" && flag <= "9" => {
-            let a = flag.parse::<i32>().unwrap();
-            Message::OpponentPlay {
-                number: a,
-                card: convert_string_to_card(card),
+        "hand" => {
+            if split.len() > 10 {
+                return Err(ParseError::WrongTokenCount {
+                    found: split.len(),
+                    expected: "player <direction> hand with at most 7 cards",
+                });
             }
+            Ok(Message::PlayerHand {
+                direction,
+                cards: convert_cards(&split[3..], 3)?,
+            })
         }
-        &["colors", ref colors..] if colors.len() == 6 => {
-            let mut colors_vec: Vec<String> = Vec::new();
-            for i in colors {
-                colors_vec.push(String::from(*i));
-            }
-            Message::ColorNames { colors: colors_vec }
+        e => Err(invalid(e, 2, "name|hand")),
+    }
+}
+
+fn parse_flag_message(split: &[&str]) -> Result<Message, ParseError> {
+    if split.len() < 2 {
+        return Err(ParseError::WrongTokenCount {
+            found: split.len(),
+            expected: "flag claim-status|<flag 1-9> ...",
+        });
+    }
+    if split[1] == "claim-status" {
+        expect_count(split, 11, "flag claim-status with 9 claims")?;
+        let claims = split[2..]
+            .iter()
+            .enumerate()
+            .map(|(i, claim)| convert_claim_status(claim, i + 2))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(Message::FlagClaimStatus { flags_claimed: claims });
+    }
+    if split.len() < 4 {
+        return Err(ParseError::WrongTokenCount {
+            found: split.len(),
+            expected: "flag <flag 1-9> cards <direction> <card>...",
+        });
+    }
+    let flag_num = convert_flag_number(split[1], 1)?;
+    expect_token(split, 2, "cards")?;
+    Ok(Message::FlagStatus {
+        flag_num,
+        direction: convert_direction(split[3], 3)?,
+        cards: convert_cards(&split[4..], 4)?,
+    })
+}
+
+fn parse_opponent_message(split: &[&str]) -> Result<Message, ParseError> {
+    expect_count(split, 4, "opponent play <flag 1-9> <card>")?;
+    expect_token(split, 1, "play")?;
+    Ok(Message::OpponentPlay {
+        number: convert_flag_number(split[2], 2)? as i32,
+        card: convert_string_to_card(split[3], 3)?,
+    })
+}
+
+/// Parses one line from the server, describing exactly what was wrong with it on failure.
+pub fn try_parse_message(message: &str) -> Result<Message, ParseError> {
+    let split: Vec<&str> = message.split_whitespace().collect();
+    match split.first() {
+        None => Err(ParseError::Empty),
+        Some(&"go") => {
+            expect_count(&split, 2, "go play-card")?;
+            expect_token(&split, 1, "play-card")?;
+            Ok(Message::PlayCard)
         }
-        &["flag", "claim-status", ref flag_claims..] if flag_claims.len() == 9 => {
-            let mut claims = Vec::new();
-            for i in flag_claims {
-                claims.push(convert_claim_status(i));
-            }
-            Message::FlagClaimStatus { flags_claimed: claims }
+        Some(&"player") => parse_player_message(&split),
+        Some(&"flag") => parse_flag_message(&split),
+        Some(&"opponent") => parse_opponent_message(&split),
+        Some(&"colors") => {
+            expect_count(&split, 7, "colors with 6 names")?;
+            let colors = split[1..].iter().map(|i| String::from(*i)).collect();
+            Ok(Message::ColorNames { colors })
         }
-        _ => Message::Blank,
+        Some(e) => Err(ParseError::UnknownMessage { token: String::from(*e) }),
     }
 }
 
+/// Compatibility wrapper around `try_parse_message` that maps every malformed line to
+/// `Message::Blank` instead of reporting why it was rejected.
+pub fn parse_message(message: String) -> Message {
+    try_parse_message(&message).unwrap_or(Message::Blank)
+}
+
 #[cfg(test)]
 mod test_parsing_messages {
     use super::*;
@@ -298,4 +523,197 @@ mod test_parsing_messages {
             _ => panic!("Wrong Card type."),
         }
     }
+
+    #[test]
+    fn card_without_number_is_an_error() {
+        let x = try_parse_message("opponent play 3 red");
+        assert_eq!(Err(ParseError::InvalidToken {
+                       token: String::from("red"),
+                       position: 3,
+                       expected: "<color>,<number>",
+                   }),
+                   x);
+    }
+
+    #[test]
+    fn card_with_bad_number_is_an_error() {
+        let x = try_parse_message("player north hand red,3 red,x");
+        assert_eq!(Err(ParseError::InvalidToken {
+                       token: String::from("red,x"),
+                       position: 4,
+                       expected: "<color>,<number>",
+                   }),
+                   x);
+    }
+
+    #[test]
+    fn bad_direction_is_an_error() {
+        let x = try_parse_message("flag 2 cards east red,3");
+        assert_eq!(Err(ParseError::InvalidToken {
+                       token: String::from("east"),
+                       position: 3,
+                       expected: "north|south",
+                   }),
+                   x);
+    }
+
+    #[test]
+    fn bad_claim_status_is_an_error() {
+        let x = try_parse_message("flag claim-status north south unclaimed unclaimed south \
+                                   north south west unclaimed");
+        assert_eq!(Err(ParseError::InvalidToken {
+                       token: String::from("west"),
+                       position: 9,
+                       expected: "north|south|unclaimed",
+                   }),
+                   x);
+    }
+
+    #[test]
+    fn flag_out_of_range_is_an_error() {
+        let x = try_parse_message("opponent play 10 red,5");
+        assert_eq!(Err(ParseError::InvalidToken {
+                       token: String::from("10"),
+                       position: 2,
+                       expected: "<flag 1-9>",
+                   }),
+                   x);
+    }
+
+    #[test]
+    fn wrong_token_count_is_an_error() {
+        match try_parse_message("colors a b c") {
+            Err(ParseError::WrongTokenCount { found: 4, .. }) => {}
+            x => panic!("Unexpected result {:?}", x),
+        }
+        match try_parse_message("player north hand a,1 a,2 a,3 a,4 a,5 a,6 a,7 a,8") {
+            Err(ParseError::WrongTokenCount { found: 11, .. }) => {}
+            x => panic!("Unexpected result {:?}", x),
+        }
+    }
+
+    #[test]
+    fn unknown_and_empty_messages_are_errors() {
+        assert_eq!(Err(ParseError::UnknownMessage { token: String::from("this") }),
+                   try_parse_message("this isn't a message"));
+        assert_eq!(Err(ParseError::Empty), try_parse_message("   "));
+    }
+
+    #[test]
+    fn malformed_message_is_blank_with_compatibility_wrapper() {
+        match parse_message(String::from("opponent play 3 red")) {
+            Message::Blank => {}
+            _ => panic!("Wrong Card type."),
+        }
+    }
+
+    /// Small deterministic generator so the round trip tests cover many messages without
+    /// pulling in a property testing crate.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 33) % n
+        }
+    }
+
+    fn random_direction(rng: &mut Lcg) -> Direction {
+        if rng.below(2) == 0 {
+            Direction::North
+        } else {
+            Direction::South
+        }
+    }
+
+    fn random_cards(rng: &mut Lcg, max: u64) -> Vec<CardString> {
+        let colors = ["red", "blue", "green", "yellow", "purple", "orange"];
+        (0..rng.below(max + 1))
+            .map(|_| {
+                CardString {
+                    color: String::from(colors[rng.below(6) as usize]),
+                    number: rng.below(10) as i32 + 1,
+                }
+            })
+            .collect()
+    }
+
+    fn random_message(rng: &mut Lcg) -> Message {
+        match rng.below(8) {
+            0 => Message::Blank,
+            1 => Message::PlayerDirection { direction: random_direction(rng) },
+            2 => {
+                Message::ColorNames {
+                    colors: (0..6).map(|i| format!("c{}x{}", i, rng.below(100))).collect(),
+                }
+            }
+            3 => {
+                let claims = [ClaimStatus::Unclaimed, ClaimStatus::North, ClaimStatus::South];
+                Message::FlagClaimStatus {
+                    flags_claimed: (0..9).map(|_| claims[rng.below(3) as usize].clone()).collect(),
+                }
+            }
+            4 => {
+                Message::FlagStatus {
+                    flag_num: rng.below(9) as u32 + 1,
+                    direction: random_direction(rng),
+                    cards: random_cards(rng, 3),
+                }
+            }
+            5 => {
+                Message::OpponentPlay {
+                    number: rng.below(9) as i32 + 1,
+                    card: random_cards(rng, 1).pop().unwrap_or(CardString {
+                        color: String::from("red"),
+                        number: 1,
+                    }),
+                }
+            }
+            6 => {
+                Message::PlayerHand {
+                    direction: random_direction(rng),
+                    cards: random_cards(rng, 7),
+                }
+            }
+            _ => Message::PlayCard,
+        }
+    }
+
+    #[test]
+    fn format_messages_as_server_lines() {
+        let message = Message::FlagStatus {
+            flag_num: 3,
+            direction: Direction::North,
+            cards: vec![CardString {
+                            color: String::from("red"),
+                            number: 3,
+                        },
+                        CardString {
+                            color: String::from("blue"),
+                            number: 9,
+                        }],
+        };
+        assert_eq!("flag 3 cards north red,3 blue,9", message.to_string());
+        let message = Message::FlagStatus {
+            flag_num: 7,
+            direction: Direction::South,
+            cards: vec![],
+        };
+        assert_eq!("flag 7 cards south", message.to_string());
+        assert_eq!("go play-card", Message::PlayCard.to_string());
+        assert_eq!("", Message::Blank.to_string());
+    }
+
+    #[test]
+    fn parse_of_format_round_trips() {
+        let mut rng = Lcg(7);
+        for _ in 0..5000 {
+            let message = random_message(&mut rng);
+            let line = message.to_string();
+            assert_eq!(message, parse_message(line.clone()), "line '{}'", line);
+            if message != Message::Blank {
+                assert_eq!(Ok(message), try_parse_message(&line));
+            }
+        }
+    }
 }
@@ -0,0 +1,300 @@
+//! A one line notation for positions, handy in bug reports and puzzle tests.
+//!
+//! Five fields separated by spaces:
+//!
+//! 1. our direction, `n` or `s`
+//! 2. the six color names separated by commas, written as `referee::DEFAULT_COLORS` when the
+//!    state has not been told them yet
+//! 3. the nine flags separated by `/`, each as `<north cards>|<south cards>` with at most three
+//!    cards a side
+//! 4. the claim status of the nine flags, `-` for unclaimed, `n` or `s`
+//! 5. our hand, or `-` when it is empty
+//!
+//! What we have inferred about the opponent's hand is not part of the notation; a position
+//! read back starts with a fresh `OpponentModel`.
+//!
+//! A card is the letter of its color, `a` for the first color up to `f`, followed by its
+//! number, so `a3b7` is the third and seventh cards of the first two colors. Numbers run from
+//! 1 to `board::MAX_NUMBER` and no card may appear twice. For example:
+//!
+//! `n red,orange,yellow,green,blue,purple a3b7|c1/|/|/|/|/|/|/|/| -s------- d4e9`
+
+use board;
+use game_state::GameState;
+use message_parsing::{Card, ClaimStatus, Color, Direction};
+use referee::DEFAULT_COLORS;
+use std::error;
+use std::fmt;
+
+const COLOR_LETTERS: &str = "abcdef";
+
+/// Which field of a notation string could not be read.
+#[derive(PartialEq, Debug, Clone)]
+pub struct NotationError {
+    pub field: &'static str,
+    pub text: String,
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid {} '{}' in position notation", self.field, self.text)
+    }
+}
+
+impl error::Error for NotationError {}
+
+fn error(field: &'static str, text: &str) -> NotationError {
+    NotationError {
+        field,
+        text: String::from(text),
+    }
+}
+
+fn write_cards(cards: &[Card]) -> String {
+    cards.iter()
+        .map(|card| {
+            let letter = COLOR_LETTERS.as_bytes()[card.color.index()] as char;
+            format!("{}{}", letter, card.number)
+        })
+        .collect()
+}
+
+fn read_cards(text: &str) -> Result<Vec<Card>, NotationError> {
+    let mut cards = vec![];
+    let mut rest = text;
+    while let Some(letter) = rest.chars().next() {
+        let color = COLOR_LETTERS.find(letter)
+            .and_then(Color::from_index)
+            .ok_or_else(|| error("card", rest))?;
+        let digits = rest[1..].chars().take_while(|c| c.is_ascii_digit()).count();
+        let number = rest[1..1 + digits]
+            .parse::<i32>()
+            .ok()
+            .filter(|number| *number >= 1 && *number <= board::MAX_NUMBER)
+            .ok_or_else(|| error("card", rest))?;
+        cards.push(Card {
+            color,
+            number,
+        });
+        rest = &rest[1 + digits..];
+    }
+    Ok(cards)
+}
+
+fn claim_char(claim: &ClaimStatus) -> char {
+    match *claim {
+        ClaimStatus::Unclaimed => '-',
+        ClaimStatus::North => 'n',
+        ClaimStatus::South => 's',
+    }
+}
+
+impl GameState {
+    /// Writes the position in the notation described in the `notation` module.
+    pub fn to_notation(&self) -> String {
+        let direction = match self.player_direction {
+            Direction::North => "n",
+            Direction::South => "s",
+        };
+        let (north, south) = match self.player_direction {
+            Direction::North => (&self.player_side, &self.opponent_side),
+            Direction::South => (&self.opponent_side, &self.player_side),
+        };
+        let flags: Vec<String> = (0..9)
+            .map(|flag| {
+                format!("{}|{}",
+                        write_cards(north.get(flag).map_or(&[][..], |cards| &cards[..])),
+                        write_cards(south.get(flag).map_or(&[][..], |cards| &cards[..])))
+            })
+            .collect();
+        let claims: String = (0..9)
+            .map(|flag| self.claim_status.get(flag).map_or('-', claim_char))
+            .collect();
+        let hand = if self.player_hand.is_empty() {
+            String::from("-")
+        } else {
+            write_cards(&self.player_hand)
+        };
+        let colors = if self.colors.len() == 6 {
+            self.colors.join(",")
+        } else {
+            DEFAULT_COLORS.join(",")
+        };
+        format!("{} {} {} {} {}",
+                direction,
+                colors,
+                flags.join("/"),
+                claims,
+                hand)
+    }
+
+    /// Reads a position written by `to_notation`. The deck is rebuilt as every card not on the
+    /// board or in our hand.
+    pub fn from_notation(text: &str) -> Result<GameState, NotationError> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(error("field count", text));
+        }
+        let player_direction = match fields[0] {
+            "n" => Direction::North,
+            "s" => Direction::South,
+            e => return Err(error("direction", e)),
+        };
+        let colors: Vec<String> = fields[1].split(',').map(String::from).collect();
+        if colors.len() != 6 {
+            return Err(error("colors", fields[1]));
+        }
+        let flags: Vec<&str> = fields[2].split('/').collect();
+        if flags.len() != 9 {
+            return Err(error("flags", fields[2]));
+        }
+        let mut north = vec![];
+        let mut south = vec![];
+        for flag in flags {
+            let mut sides = flag.split('|');
+            match (sides.next(), sides.next(), sides.next()) {
+                (Some(n), Some(s), None) => {
+                    let (n, s) = (read_cards(n)?, read_cards(s)?);
+                    if n.len() > 3 || s.len() > 3 {
+                        return Err(error("flag", flag));
+                    }
+                    north.push(n);
+                    south.push(s);
+                }
+                _ => return Err(error("flag", flag)),
+            }
+        }
+        let claim_status = fields[3]
+            .chars()
+            .map(|c| match c {
+                '-' => Ok(ClaimStatus::Unclaimed),
+                'n' => Ok(ClaimStatus::North),
+                's' => Ok(ClaimStatus::South),
+                _ => Err(error("claims", fields[3])),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if claim_status.len() != 9 {
+            return Err(error("claims", fields[3]));
+        }
+        let player_hand = match fields[4] {
+            "-" => vec![],
+            hand => read_cards(hand)?,
+        };
+        let (player_side, opponent_side) = match player_direction {
+            Direction::North => (north, south),
+            Direction::South => (south, north),
+        };
+        let seen: Vec<Card> = player_side.iter()
+            .chain(opponent_side.iter())
+            .flat_map(|cards| cards.iter().cloned())
+            .chain(player_hand.iter().cloned())
+            .collect();
+        for (index, card) in seen.iter().enumerate() {
+            if seen[..index].contains(card) {
+                return Err(error("card", &write_cards(&[*card])));
+            }
+        }
+        Ok(GameState {
+            player_direction,
+            opponent_direction: board::other_direction(player_direction),
+            deck: board::full_deck().into_iter().filter(|card| !seen.contains(card)).collect(),
+            colors_vec: (0..6).filter_map(Color::from_index).collect(),
+            colors,
+            claim_status,
+            opponent_side,
+            player_side,
+            player_hand,
+            opponent_model: Default::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_notation {
+    use super::*;
+    use game_state::GameHandler;
+    use referee::Referee;
+    use std::io;
+    use test_support::FirstMoveBot;
+
+    const EXAMPLE: &str = "n red,orange,yellow,green,blue,purple a3b7|c1/|/|/|/|/|/|/|/| -s------- \
+                           d4e9";
+
+    #[test]
+    fn read_example_position() {
+        let state = GameState::from_notation(EXAMPLE).unwrap();
+        assert_eq!(Direction::North, state.player_direction);
+        assert_eq!(Direction::South, state.opponent_direction);
+        assert_eq!(vec![Card {
+                            color: Color::Color1,
+                            number: 3,
+                        },
+                        Card {
+                            color: Color::Color2,
+                            number: 7,
+                        }],
+                   state.player_side[0]);
+        assert_eq!(1, state.opponent_side[0].len());
+        assert_eq!(ClaimStatus::South, state.claim_status[1]);
+        assert_eq!(2, state.player_hand.len());
+        assert_eq!(49, state.deck.len());
+        assert_eq!(EXAMPLE, state.to_notation());
+    }
+
+    #[test]
+    fn notation_round_trips_states_built_from_protocol() {
+        let mut referee = Referee::shuffled(12);
+        for turn in 0..12 {
+            let direction = if turn % 2 == 0 {
+                Direction::North
+            } else {
+                Direction::South
+            };
+            let mut handler = GameHandler::with_output(io::sink());
+            handler.run_one_round(&FirstMoveBot, format!("player {} name", direction)).unwrap();
+            handler.run_one_round(&FirstMoveBot,
+                                  String::from("colors red orange yellow green blue purple"))
+                .unwrap();
+            for line in referee.turn_lines(direction) {
+                handler.run_one_round(&FirstMoveBot, line).unwrap();
+            }
+            let notation = handler.state.to_notation();
+            let mut expected = GameState::from_notation(&notation).unwrap();
+            expected.opponent_model = handler.state.opponent_model.clone();
+            assert_eq!(handler.state, expected);
+            let chosen = handler.state.legal_moves().next().unwrap();
+            referee.apply_reply(direction, &chosen.to_command().to_wire(&handler.state)).unwrap();
+        }
+    }
+
+    #[test]
+    fn bad_notation_is_an_error() {
+        assert_eq!(Err(error("direction", "x")),
+                   GameState::from_notation("x a,b,c,d,e,f |/|/|/|/|/|/|/|/| --------- -"));
+        assert_eq!(Err(error("card", "z1")),
+                   GameState::from_notation("n a,b,c,d,e,f z1|/|/|/|/|/|/|/|/| --------- -"));
+        assert_eq!(Err(error("claims", "----")),
+                   GameState::from_notation("n a,b,c,d,e,f |/|/|/|/|/|/|/|/| ---- -"));
+        assert!(GameState::from_notation("n a,b,c,d,e,f").is_err());
+    }
+
+    #[test]
+    fn impossible_positions_are_errors() {
+        assert_eq!(Err(error("card", "a10")),
+                   GameState::from_notation("n a,b,c,d,e,f a10|/|/|/|/|/|/|/|/| --------- -"));
+        assert_eq!(Err(error("card", "b0")),
+                   GameState::from_notation("n a,b,c,d,e,f |/|/|/|/|/|/|/|/| --------- a1b0"));
+        assert_eq!(Err(error("card", "c4")),
+                   GameState::from_notation("n a,b,c,d,e,f c4|/|/|/|/|/|/|/|/| --------- c4"));
+        assert_eq!(Err(error("flag", "a1a2a3a4|")),
+                   GameState::from_notation("n a,b,c,d,e,f a1a2a3a4|/|/|/|/|/|/|/|/| --------- -"));
+    }
+
+    #[test]
+    fn default_state_round_trips() {
+        let state = GameState::default();
+        let read = GameState::from_notation(&state.to_notation()).unwrap();
+        assert_eq!(DEFAULT_COLORS.to_vec(), read.colors);
+        assert_eq!(read.to_notation(), state.to_notation());
+    }
+}
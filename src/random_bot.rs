@@ -0,0 +1,77 @@
+//! A bot that plays any legal move, for baselines and for fuzzing `GameHandler`.
+
+use game_state::{AiInterface, GameState, Move};
+use rng::{RandomSource, SeededRng};
+use std::cell::RefCell;
+
+/// Picks uniformly among `GameState::legal_moves`. Two bots built from the same seed make
+/// the same choices in the same games.
+pub struct RandomBot {
+    rng: RefCell<SeededRng>,
+}
+
+impl RandomBot {
+    pub fn new(seed: u64) -> RandomBot {
+        RandomBot { rng: RefCell::new(SeededRng::new(seed)) }
+    }
+}
+
+impl AiInterface for RandomBot {
+    fn choose_move(&self, state: &GameState) -> Option<Move> {
+        let moves: Vec<Move> = state.legal_moves().collect();
+        if moves.is_empty() {
+            return None;
+        }
+        Some(moves[self.rng.borrow_mut().below(moves.len())])
+    }
+
+    fn get_bot_name(&self) -> String {
+        String::from("random")
+    }
+}
+
+#[cfg(test)]
+mod test_random_bot {
+    use super::*;
+    use board::WinReason;
+    use match_runner;
+
+    #[test]
+    fn same_seed_same_choices() {
+        let state = GameState::from_notation("n red,orange,yellow,green,blue,purple \
+                                              |/|/|/|/|/|/|/|/| --------- a1b2c3d4e5f6a7")
+            .unwrap();
+        let a = RandomBot::new(4);
+        let b = RandomBot::new(4);
+        let mut seen = vec![];
+        for _ in 0..20 {
+            let chosen = a.choose_move(&state).unwrap();
+            assert_eq!(Some(chosen), b.choose_move(&state));
+            assert!(state.is_legal_move(&chosen));
+            if !seen.contains(&chosen) {
+                seen.push(chosen);
+            }
+        }
+        assert!(seen.len() > 5);
+    }
+
+    #[test]
+    fn no_move_without_legal_moves() {
+        let state = GameState::from_notation("n red,orange,yellow,green,blue,purple \
+                                              |/|/|/|/|/|/|/|/| --------- -")
+            .unwrap();
+        assert_eq!(None, RandomBot::new(1).choose_move(&state));
+        assert_eq!(None, RandomBot::new(1).choose_move(&GameState::default()));
+    }
+
+    #[test]
+    fn random_games_run_to_the_end() {
+        for seed in 0..20 {
+            let result = match_runner::play_match(&RandomBot::new(seed),
+                                                  &RandomBot::new(seed + 100),
+                                                  seed);
+            assert!(!matches!(result.reason, WinReason::Forfeit(_)));
+            assert!(result.moves.len() >= 15);
+        }
+    }
+}
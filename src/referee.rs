@@ -0,0 +1,409 @@
+use board::{self, Board, WinReason, HAND_SIZE};
+use command::Command;
+use game_state::FlagIndex;
+use message_parsing::{Card, CardString, Direction, Message};
+use rng::{self, SeededRng};
+
+/// Color names sent in the `colors` message unless the referee is given others.
+pub const DEFAULT_COLORS: [&str; 6] = ["red", "orange", "yellow", "green", "blue", "purple"];
+
+/// Why a player could not be reached.
+#[derive(PartialEq, Debug, Clone)]
+pub enum SeatError {
+    /// No reply arrived within the time allowed.
+    Timeout,
+    /// The player went away, for instance its process exited or closed its pipes.
+    Disconnected,
+}
+
+/// One player as the referee sees it: something that takes protocol lines and answers some.
+pub trait Seat {
+    /// Sends a line that needs no reply.
+    fn tell(&mut self, line: &str) -> Result<(), SeatError>;
+    /// Sends a line and waits for the reply.
+    fn ask(&mut self, line: &str) -> Result<String, SeatError>;
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct PlayedMove {
+    pub direction: Direction,
+    pub flag: FlagIndex,
+    pub card: Card,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct GameResult {
+    /// `None` for a draw.
+    pub winner: Option<Direction>,
+    pub reason: WinReason,
+    /// The board as it stood when the game ended.
+    pub board: Board,
+    pub moves: Vec<PlayedMove>,
+}
+
+/// Runs one game of Battle Line between two seats, speaking the same protocol as the server.
+pub struct Referee {
+    colors: Vec<String>,
+    deck: Vec<Card>,
+    north_hand: Vec<Card>,
+    south_hand: Vec<Card>,
+    names: Vec<String>,
+    board: Board,
+    moves: Vec<PlayedMove>,
+    seed: Option<u64>,
+}
+
+impl Referee {
+    /// Deals seven cards to each player from the front of `deck`, north first.
+    pub fn new(colors: Vec<String>, mut deck: Vec<Card>) -> Referee {
+        let mut north_hand = vec![];
+        let mut south_hand = vec![];
+        for _ in 0..HAND_SIZE {
+            for hand in &mut [&mut north_hand, &mut south_hand] {
+                if !deck.is_empty() {
+                    hand.push(deck.remove(0));
+                }
+            }
+        }
+        Referee {
+            colors,
+            deck,
+            north_hand,
+            south_hand,
+            names: vec![String::new(), String::new()],
+            board: Default::default(),
+            moves: vec![],
+            seed: None,
+        }
+    }
+    /// A referee using `DEFAULT_COLORS` and the full deck shuffled from `seed`.
+    pub fn shuffled(seed: u64) -> Referee {
+        let mut deck = board::full_deck();
+        rng::shuffle(&mut SeededRng::new(seed), &mut deck);
+        let mut referee = Referee::new(DEFAULT_COLORS.iter().map(|c| String::from(*c)).collect(),
+                                       deck);
+        referee.seed = Some(seed);
+        referee
+    }
+    /// The seed the deck was shuffled from, `None` when the deck was given to `new`.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+    pub fn deck(&self) -> &[Card] {
+        &self.deck
+    }
+    pub fn hand(&self, direction: Direction) -> &Vec<Card> {
+        match direction {
+            Direction::North => &self.north_hand,
+            Direction::South => &self.south_hand,
+        }
+    }
+    /// The name a player gave when asked, empty before that.
+    pub fn name(&self, direction: Direction) -> &str {
+        &self.names[direction as usize]
+    }
+    pub fn moves(&self) -> &[PlayedMove] {
+        &self.moves
+    }
+    fn hand_mut(&mut self, direction: Direction) -> &mut Vec<Card> {
+        match direction {
+            Direction::North => &mut self.north_hand,
+            Direction::South => &mut self.south_hand,
+        }
+    }
+    fn card_string(&self, card: &Card) -> CardString {
+        CardString {
+            color: self.colors[card.color.index()].clone(),
+            number: card.number,
+        }
+    }
+    fn card_strings(&self, cards: &[Card]) -> Vec<CardString> {
+        cards.iter().map(|card| self.card_string(card)).collect()
+    }
+    /// Cards that have not reached the board yet, the pool flag proofs are made against.
+    fn unplayed(&self) -> Vec<Card> {
+        let mut unplayed = self.deck.clone();
+        unplayed.extend(self.north_hand.iter().cloned());
+        unplayed.extend(self.south_hand.iter().cloned());
+        unplayed
+    }
+    pub fn can_move(&self, direction: Direction) -> bool {
+        !self.hand(direction).is_empty() && !self.board.open_flags(direction).is_empty()
+    }
+    /// The lines sent to `direction` before asking for its move: the opponent's last play, the
+    /// hand, the claim status and both sides of every flag.
+    pub fn turn_lines(&self, direction: Direction) -> Vec<String> {
+        let mut lines = vec![];
+        if let Some(last) = self.moves.last() {
+            if last.direction != direction {
+                lines.push(Message::OpponentPlay {
+                        number: last.flag as i32 + 1,
+                        card: self.card_string(&last.card),
+                    }
+                    .to_string());
+            }
+        }
+        lines.push(Message::PlayerHand {
+                direction,
+                cards: self.card_strings(self.hand(direction)),
+            }
+            .to_string());
+        lines.push(Message::FlagClaimStatus { flags_claimed: self.board.claims.clone() }
+            .to_string());
+        for flag in 0..self.board.claims.len() {
+            for side in &[Direction::North, Direction::South] {
+                lines.push(Message::FlagStatus {
+                        flag_num: flag as u32 + 1,
+                        direction: *side,
+                        cards: self.card_strings(&self.board.side(*side)[flag]),
+                    }
+                    .to_string());
+            }
+        }
+        lines
+    }
+    /// Checks a reply to `go play-card` and applies it: the card goes to the flag, a new card
+    /// is drawn and any flags that can be decided are awarded.
+    pub fn apply_reply(&mut self, direction: Direction, reply: &str) -> Result<(), String> {
+        let (flag, card) = match Command::from_wire(reply, &self.colors) {
+            Ok(Command::PlayCard { flag, card }) => (flag as FlagIndex - 1, card),
+            Ok(_) => return Err(format!("expected a play, got '{}'", reply)),
+            Err(e) => return Err(format!("malformed reply '{}': {}", reply, e)),
+        };
+        let index = match self.hand(direction).iter().position(|c| *c == card) {
+            Some(index) => index,
+            None => return Err(format!("played a card not in hand: '{}'", reply)),
+        };
+        if !self.board.is_open(direction, flag) {
+            return Err(format!("played to a full or claimed flag: '{}'", reply));
+        }
+        self.hand_mut(direction).remove(index);
+        self.board.play(direction, flag, card);
+        self.moves.push(PlayedMove {
+            direction,
+            flag,
+            card,
+        });
+        if !self.deck.is_empty() {
+            let drawn = self.deck.remove(0);
+            self.hand_mut(direction).push(drawn);
+        }
+        let unplayed = self.unplayed();
+        self.board.resolve_flags(&unplayed);
+        Ok(())
+    }
+    fn greet(&mut self, seat: &mut dyn Seat, direction: Direction) -> Result<(), String> {
+        let reply = seat.ask(&Message::PlayerDirection { direction }.to_string())
+            .map_err(describe)?;
+        match Command::from_wire(&reply, &self.colors) {
+            Ok(Command::PlayerName { direction: d, name }) if d == direction => {
+                self.names[direction as usize] = name;
+                Ok(())
+            }
+            _ => Err(format!("bad name reply '{}'", reply)),
+        }
+    }
+    fn take_turn(&mut self, seat: &mut dyn Seat, direction: Direction) -> Result<(), String> {
+        for line in self.turn_lines(direction) {
+            seat.tell(&line).map_err(describe)?;
+        }
+        let reply = seat.ask(&Message::PlayCard.to_string()).map_err(describe)?;
+        self.apply_reply(direction, &reply)
+    }
+    fn finish(&self, winner: Option<Direction>, reason: WinReason) -> GameResult {
+        GameResult {
+            winner,
+            reason,
+            board: self.board.clone(),
+            moves: self.moves.clone(),
+        }
+    }
+    fn forfeit(&self, direction: Direction, reason: String) -> GameResult {
+        self.finish(Some(board::other_direction(direction)),
+                    WinReason::Forfeit(format!("{}: {}", direction, reason)))
+    }
+    /// Plays the game to the end, north moving first. A player that cannot be reached or sends
+    /// anything but a legal reply forfeits.
+    pub fn run(&mut self, north: &mut dyn Seat, south: &mut dyn Seat) -> GameResult {
+        for direction in &[Direction::North, Direction::South] {
+            let seat: &mut dyn Seat = match *direction {
+                Direction::North => &mut *north,
+                Direction::South => &mut *south,
+            };
+            if let Err(reason) = self.greet(seat, *direction) {
+                return self.forfeit(*direction, reason);
+            }
+        }
+        let colors = Message::ColorNames { colors: self.colors.clone() }.to_string();
+        for direction in &[Direction::North, Direction::South] {
+            let seat: &mut dyn Seat = match *direction {
+                Direction::North => &mut *north,
+                Direction::South => &mut *south,
+            };
+            if let Err(e) = seat.tell(&colors) {
+                return self.forfeit(*direction, describe(e));
+            }
+        }
+        let mut direction = Direction::North;
+        let mut passes = 0;
+        loop {
+            if let Some((winner, reason)) = self.board.game_over(|| passes == 2) {
+                return self.finish(winner, reason);
+            }
+            if !self.can_move(direction) {
+                passes += 1;
+                direction = board::other_direction(direction);
+                continue;
+            }
+            passes = 0;
+            let seat: &mut dyn Seat = match direction {
+                Direction::North => &mut *north,
+                Direction::South => &mut *south,
+            };
+            if let Err(reason) = self.take_turn(seat, direction) {
+                return self.forfeit(direction, reason);
+            }
+            direction = board::other_direction(direction);
+        }
+    }
+}
+
+fn describe(error: SeatError) -> String {
+    match error {
+        SeatError::Timeout => String::from("timed out"),
+        SeatError::Disconnected => String::from("disconnected"),
+    }
+}
+
+#[cfg(test)]
+mod test_referee {
+    use super::*;
+    use match_runner::HandlerSeat;
+    use message_parsing::Color;
+    use test_support::{card, FirstMoveBot};
+
+    /// Answers every question with the same line.
+    struct ScriptedSeat {
+        reply: String,
+    }
+
+    impl Seat for ScriptedSeat {
+        fn tell(&mut self, _: &str) -> Result<(), SeatError> {
+            Ok(())
+        }
+
+        fn ask(&mut self, _: &str) -> Result<String, SeatError> {
+            Ok(self.reply.clone())
+        }
+    }
+
+    #[test]
+    fn deal_seven_cards_each() {
+        let referee = Referee::shuffled(3);
+        assert_eq!(7, referee.hand(Direction::North).len());
+        assert_eq!(7, referee.hand(Direction::South).len());
+        assert_eq!(40, referee.deck().len());
+    }
+
+    #[test]
+    fn same_seed_same_deal() {
+        assert_eq!(Referee::shuffled(9).deck(), Referee::shuffled(9).deck());
+        assert!(Referee::shuffled(9).deck() != Referee::shuffled(10).deck());
+    }
+
+    #[test]
+    fn turn_lines_parse_as_messages() {
+        let referee = Referee::shuffled(1);
+        let lines = referee.turn_lines(Direction::North);
+        assert_eq!(20, lines.len());
+        assert!(lines[0].starts_with("player north hand "));
+        for line in &lines {
+            assert!(::message_parsing::try_parse_message(line).is_ok(), "line '{}'", line);
+        }
+    }
+
+    #[test]
+    fn full_game_between_handlers() {
+        let mut referee = Referee::shuffled(42);
+        let result = referee.run(&mut HandlerSeat::new(&FirstMoveBot),
+                                 &mut HandlerSeat::new(&FirstMoveBot));
+        match result.reason {
+            WinReason::Envelopment | WinReason::Breakthrough => {}
+            ref reason => panic!("Unexpected reason {:?}", reason),
+        }
+        assert_eq!(Some((result.winner, result.reason.clone())),
+                   result.board.game_over(|| false));
+        assert_eq!("first", referee.name(Direction::South));
+        assert_eq!(Direction::North, result.moves[0].direction);
+        assert_eq!(Direction::South, result.moves[1].direction);
+    }
+
+    #[test]
+    fn non_play_reply_forfeits() {
+        let mut referee = Referee::shuffled(5);
+        let mut north = ScriptedSeat { reply: String::from("player north scripted") };
+        let result = referee.run(&mut north, &mut HandlerSeat::new(&FirstMoveBot));
+        assert_eq!(Some(Direction::South), result.winner);
+        match result.reason {
+            WinReason::Forfeit(ref reason) => assert!(reason.starts_with("north:")),
+            ref reason => panic!("Unexpected reason {:?}", reason),
+        }
+        assert!(result.moves.is_empty());
+    }
+
+    #[test]
+    fn illegal_replies_are_rejected() {
+        let mut referee = Referee::shuffled(5);
+        let south_card = referee.hand(Direction::South)[0];
+        let north_card = referee.hand(Direction::North)[0];
+        let play = |card: Card| {
+            format!("play 1 {},{}", DEFAULT_COLORS[card.color.index()], card.number)
+        };
+        assert!(referee.apply_reply(Direction::North, &play(south_card)).is_err());
+        assert!(referee.apply_reply(Direction::North, "play 1 red").is_err());
+        assert_eq!(Ok(()), referee.apply_reply(Direction::North, &play(north_card)));
+        assert_eq!(1, referee.board().north[0].len());
+        assert_eq!(7, referee.hand(Direction::North).len());
+        assert_eq!(39, referee.deck().len());
+    }
+
+    #[test]
+    fn bad_name_reply_forfeits() {
+        let mut referee = Referee::shuffled(5);
+        let mut north = HandlerSeat::new(&FirstMoveBot);
+        let mut south = ScriptedSeat { reply: String::from("player north impostor") };
+        let result = referee.run(&mut north, &mut south);
+        assert_eq!(Some(Direction::North), result.winner);
+    }
+
+    #[test]
+    fn stuck_players_with_equal_flags_draw() {
+        // Only enough cards to deal, so both players are stuck once their hands are played out.
+        // North's red wedge takes the first flag before south completes it, south's 6-7-8 line
+        // takes the second and the third is still open.
+        let deck = vec![card(Color::Color1, 7),
+                        card(Color::Color2, 1),
+                        card(Color::Color1, 8),
+                        card(Color::Color3, 3),
+                        card(Color::Color1, 9),
+                        card(Color::Color4, 6),
+                        card(Color::Color5, 1),
+                        card(Color::Color2, 7),
+                        card(Color::Color6, 4),
+                        card(Color::Color2, 8),
+                        card(Color::Color3, 5),
+                        card(Color::Color2, 9),
+                        card(Color::Color4, 2),
+                        card(Color::Color5, 3)];
+        let mut referee = Referee::new(DEFAULT_COLORS.iter().map(|c| String::from(*c)).collect(),
+                                       deck);
+        let result = referee.run(&mut HandlerSeat::new(&FirstMoveBot),
+                                 &mut HandlerSeat::new(&FirstMoveBot));
+        assert_eq!(None, result.winner);
+        assert_eq!(WinReason::MostFlags, result.reason);
+        assert_eq!(14, result.moves.len());
+    }
+}
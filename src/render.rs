@@ -0,0 +1,147 @@
+use game_state::GameState;
+use message_parsing::{Card, ClaimStatus, Direction};
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct RenderOptions {
+    /// Color each card with ANSI escape codes.
+    pub ansi: bool,
+}
+
+/// ANSI foreground code for a color, by name when it is a common one and otherwise by its
+/// position in the `colors` message.
+fn ansi_code(name: &str, index: usize) -> u8 {
+    match name {
+        "red" => 31,
+        "green" => 32,
+        "yellow" => 33,
+        "blue" => 34,
+        "purple" | "magenta" => 35,
+        "cyan" => 36,
+        "orange" => 91,
+        _ => [31, 32, 33, 34, 35, 36][index % 6],
+    }
+}
+
+fn card_label(state: &GameState, card: &Card) -> String {
+    match state.colors.get(card.color.index()) {
+        Some(name) => format!("{}{}", name, card.number),
+        None => format!("?{}", card.number),
+    }
+}
+
+fn paint(state: &GameState, card: &Card, text: String, options: &RenderOptions) -> String {
+    if !options.ansi {
+        return text;
+    }
+    let name = state.colors.get(card.color.index()).map_or("", |name| &name[..]);
+    format!("\x1b[{}m{}\x1b[0m", ansi_code(name, card.color.index()), text)
+}
+
+fn claim_label(state: &GameState, claim: Option<&ClaimStatus>) -> &'static str {
+    let ours = match state.player_direction {
+        Direction::North => ClaimStatus::North,
+        Direction::South => ClaimStatus::South,
+    };
+    match claim {
+        None | Some(&ClaimStatus::Unclaimed) => "--",
+        Some(claim) if *claim == ours => "vv",
+        Some(_) => "^^",
+    }
+}
+
+/// Draws the board as text: the opponent's cards above the flags, the claim markers in the
+/// middle, our cards below and our hand at the bottom. A won flag is marked `vv`, pointing at
+/// us, and a lost one `^^`.
+pub fn render(state: &GameState, options: &RenderOptions) -> String {
+    let cell = |card: Option<&Card>, width: usize| -> String {
+        match card {
+            Some(card) => {
+                let label = format!("{:^width$}", card_label(state, card), width = width);
+                paint(state, card, label, options)
+            }
+            None => " ".repeat(width),
+        }
+    };
+    let width = state.player_side
+        .iter()
+        .chain(state.opponent_side.iter())
+        .flat_map(|cards| cards.iter())
+        .map(|card| card_label(state, card).len())
+        .max()
+        .unwrap_or(0)
+        .max(4) + 2;
+    let side = |cards: &Vec<Vec<Card>>, flag: usize, row: usize| {
+        cell(cards.get(flag).and_then(|cards| cards.get(row)), width)
+    };
+    let mut lines = vec![format!("Opponent ({})", state.opponent_direction)];
+    for row in (0..3).rev() {
+        lines.push((0..9).map(|flag| side(&state.opponent_side, flag, row)).collect());
+    }
+    lines.push((0..9)
+        .map(|flag| format!("{:^width$}", flag + 1, width = width))
+        .collect());
+    lines.push((0..9)
+        .map(|flag| {
+            let claim = claim_label(state, state.claim_status.get(flag));
+            format!("{:^width$}", claim, width = width)
+        })
+        .collect());
+    for row in 0..3 {
+        lines.push((0..9).map(|flag| side(&state.player_side, flag, row)).collect());
+    }
+    lines.push(format!("You ({})", state.player_direction));
+    let hand: Vec<String> = state.player_hand
+        .iter()
+        .map(|card| paint(state, card, card_label(state, card), options))
+        .collect();
+    lines.push(format!("Hand: {}", hand.join(" ")));
+    let mut text: String = lines.iter()
+        .map(|line| line.trim_end())
+        .collect::<Vec<&str>>()
+        .join("\n");
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+mod test_render {
+    use super::*;
+
+    const POSITION: &str = "n red,orange,yellow,green,blue,purple \
+                            a3b7|c1/|/|/|/|/|/|/|/| -s------- d4e9";
+
+    #[test]
+    fn render_plain_board() {
+        let state = GameState::from_notation(POSITION).unwrap();
+        let numbers: String = (1..10).map(|n| format!("{:^9}", n)).collect();
+        let claims = format!("   --       ^^{}", "       --".repeat(7));
+        let expected = ["Opponent (south)",
+                        "",
+                        "",
+                        " yellow1",
+                        numbers.trim_end(),
+                        &claims,
+                        "  red3",
+                        " orange7",
+                        "",
+                        "You (north)",
+                        "Hand: green4 blue9",
+                        ""]
+            .join("\n");
+        assert_eq!(expected, render(&state, &Default::default()));
+    }
+
+    #[test]
+    fn render_ansi_colors() {
+        let state = GameState::from_notation(POSITION).unwrap();
+        let text = render(&state, &RenderOptions { ansi: true });
+        assert!(text.contains("\x1b[31m  red3   \x1b[0m"));
+        assert!(text.contains("Hand: \x1b[32mgreen4\x1b[0m \x1b[34mblue9\x1b[0m"));
+    }
+
+    #[test]
+    fn render_empty_state() {
+        let text = render(&Default::default(), &Default::default());
+        assert_eq!(11, text.lines().count());
+    }
+}
@@ -0,0 +1,98 @@
+/// Source of randomness for dealing and for bots that play randomly.
+///
+/// Everything random in this crate goes through this trait so that a game can be reproduced
+/// exactly from its seed.
+pub trait RandomSource {
+    fn next_u64(&mut self) -> u64;
+
+    /// A number in `0..bound`. `bound` must not be zero.
+    fn below(&mut self, bound: usize) -> usize {
+        // Multiply and keep the high half, which avoids most of the bias of a plain modulo.
+        ((self.next_u64() as u128 * bound as u128) >> 64) as usize
+    }
+
+    /// A number in `0.0..1.0`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// SplitMix64 seeded with a single `u64`. The sequence for a seed never changes between
+/// versions or platforms.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SeededRng {
+    seed: u64,
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> SeededRng {
+        SeededRng {
+            seed,
+            state: seed,
+        }
+    }
+    /// The seed this generator started from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RandomSource for SeededRng {
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Fisher-Yates shuffle of `items`.
+pub fn shuffle<T>(rng: &mut dyn RandomSource, items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        let j = rng.below(i + 1);
+        items.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod test_rng {
+    use super::*;
+
+    #[test]
+    fn known_sequence_for_seed() {
+        let mut rng = SeededRng::new(1234567);
+        assert_eq!(6457827717110365317, rng.next_u64());
+        assert_eq!(3203168211198807973, rng.next_u64());
+        assert_eq!(1234567, rng.seed());
+    }
+
+    #[test]
+    fn below_stays_in_range() {
+        let mut rng = SeededRng::new(8);
+        let mut seen = [false; 7];
+        for _ in 0..1000 {
+            let x = rng.below(7);
+            assert!(x < 7);
+            seen[x] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
+        for _ in 0..1000 {
+            let x = rng.next_f64();
+            assert!((0.0..1.0).contains(&x));
+        }
+    }
+
+    #[test]
+    fn shuffle_is_a_reproducible_permutation() {
+        let mut a: Vec<u32> = (0..54).collect();
+        let mut b = a.clone();
+        shuffle(&mut SeededRng::new(3), &mut a);
+        shuffle(&mut SeededRng::new(3), &mut b);
+        assert_eq!(a, b);
+        assert!(a != (0..54).collect::<Vec<u32>>());
+        a.sort();
+        assert_eq!((0..54).collect::<Vec<u32>>(), a);
+    }
+}
@@ -0,0 +1,294 @@
+//! Expectiminimax search for the endgame.
+//!
+//! Once few cards are left to draw the game tree is small enough to search properly. Our moves
+//! are maximised, the opponent's minimised and every draw from the pile is a chance node that
+//! averages over the cards that could come up. The opponent's hand is not known, so the search
+//! is run against a few hands sampled with `GameState::sample_opponent_hand` and the results
+//! averaged. Values are from our side: 1 for a won game, -1 for a lost one and 0 for a draw.
+
+use board::{self, Board};
+use evaluation::{Evaluator, FlagCount};
+use game_state::{GameState, Move};
+use message_parsing::{Card, Direction};
+use rng::SeededRng;
+use std::f64;
+use std::time::{Duration, Instant};
+
+/// Opponent hands each search is averaged over.
+pub const SAMPLES: usize = 4;
+/// The hands are sampled from a fixed seed, so the same position always gets the same answer.
+const SEED: u64 = 0x5eed;
+
+/// One sampled version of the game: our view of it in `state`, kept up to date as moves are
+/// made, plus the cards we cannot see.
+#[derive(Clone)]
+struct Node {
+    state: GameState,
+    board: Board,
+    theirs: Vec<Card>,
+    /// What is left to draw, in no particular order.
+    pile: Vec<Card>,
+    to_move: Direction,
+}
+
+impl Node {
+    fn deal(state: &GameState, rng: &mut SeededRng) -> Node {
+        let (theirs, pile) = state.sample_opponent_hand(rng);
+        let mut node = Node {
+            state: state.clone(),
+            board: Board::from_state(state),
+            theirs,
+            pile,
+            to_move: state.player_direction,
+        };
+        node.resolve();
+        node
+    }
+
+    fn is_ours(&self, direction: Direction) -> bool {
+        direction == self.state.player_direction
+    }
+
+    fn resolve(&mut self) {
+        let unplayed: Vec<Card> = self.pile
+            .iter()
+            .chain(self.theirs.iter())
+            .chain(self.state.player_hand.iter())
+            .cloned()
+            .collect();
+        self.board.resolve_flags(&unplayed);
+        self.state.claim_status = self.board.claims.clone();
+    }
+
+    fn moves(&self) -> Vec<Move> {
+        if self.is_ours(self.to_move) {
+            return self.state.legal_moves().collect();
+        }
+        let flags = self.board.open_flags(self.to_move);
+        self.theirs
+            .iter()
+            .flat_map(|card| {
+                flags.iter().map(move |flag| {
+                    Move {
+                        flag: *flag,
+                        card: *card,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// The position after `chosen`, before its player draws.
+    fn play(&self, chosen: Move) -> Node {
+        let mut next = self.clone();
+        if self.is_ours(self.to_move) {
+            next.state.player_hand.retain(|card| *card != chosen.card);
+            next.state.player_side[chosen.flag].push(chosen.card);
+        } else {
+            next.theirs.retain(|card| *card != chosen.card);
+            next.state.deck.retain(|card| *card != chosen.card);
+            next.state.opponent_side[chosen.flag].push(chosen.card);
+        }
+        next.board.play(self.to_move, chosen.flag, chosen.card);
+        next.resolve();
+        next.to_move = board::other_direction(self.to_move);
+        next
+    }
+
+    /// The position after the player who just moved drew `pile[index]`.
+    fn draw(&self, index: usize) -> Node {
+        let mut next = self.clone();
+        let card = next.pile.swap_remove(index);
+        if self.is_ours(board::other_direction(self.to_move)) {
+            next.state.player_hand.push(card);
+            next.state.deck.retain(|other| *other != card);
+        } else {
+            next.theirs.push(card);
+        }
+        next
+    }
+
+    /// The winner, `None` for a draw, once the game is over, by the same rules as
+    /// `Referee::run`.
+    fn outcome(&self) -> Option<Option<Direction>> {
+        let stuck = |direction| {
+            let mut turn = self.clone();
+            turn.to_move = direction;
+            turn.moves().is_empty()
+        };
+        self.board
+            .game_over(|| stuck(Direction::North) && stuck(Direction::South))
+            .map(|(winner, _)| winner)
+    }
+}
+
+struct Searcher<'a> {
+    evaluator: &'a dyn Evaluator,
+    /// `None` while the first depth runs, which always finishes.
+    deadline: Option<Instant>,
+}
+
+impl<'a> Searcher<'a> {
+    fn out_of_time(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Value of a decision node, or `None` if time ran out.
+    fn value(&self, node: &Node, depth: usize, mut alpha: f64, mut beta: f64) -> Option<f64> {
+        if self.out_of_time() {
+            return None;
+        }
+        if let Some(winner) = node.outcome() {
+            return Some(match winner {
+                Some(winner) if node.is_ours(winner) => 1.0,
+                Some(_) => -1.0,
+                None => 0.0,
+            });
+        }
+        if depth == 0 {
+            // Squeezed inside -1..1 so that no evaluation outranks a finished game.
+            return Some(0.9 * self.evaluator.evaluate(&node.state).tanh());
+        }
+        let moves = node.moves();
+        if moves.is_empty() {
+            let mut passed = node.clone();
+            passed.to_move = board::other_direction(node.to_move);
+            return self.value(&passed, depth, alpha, beta);
+        }
+        let maximising = node.is_ours(node.to_move);
+        let mut best = if maximising {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        };
+        for chosen in moves {
+            let value = self.chance(&node.play(chosen), depth - 1, alpha, beta)?;
+            if maximising {
+                best = best.max(value);
+                alpha = alpha.max(value);
+            } else {
+                best = best.min(value);
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        Some(best)
+    }
+
+    /// Value of the position after a move, averaged over what the mover could draw. Bounds
+    /// are not passed through the average, only on to a node with nothing to draw.
+    fn chance(&self, node: &Node, depth: usize, alpha: f64, beta: f64) -> Option<f64> {
+        if node.pile.is_empty() || node.outcome().is_some() {
+            return self.value(node, depth, alpha, beta);
+        }
+        let mut total = 0.0;
+        for index in 0..node.pile.len() {
+            total += self.value(&node.draw(index), depth, f64::NEG_INFINITY, f64::INFINITY)?;
+        }
+        Some(total / node.pile.len() as f64)
+    }
+
+    /// The best move `depth` plies deep and its value, averaged over `samples`.
+    fn root(&self, samples: &[Node], legal: &[Move], depth: usize) -> Option<(Move, f64)> {
+        let mut best: Option<(Move, f64)> = None;
+        for chosen in legal {
+            let mut total = 0.0;
+            for node in samples {
+                total += self.chance(&node.play(*chosen),
+                                     depth - 1,
+                                     f64::NEG_INFINITY,
+                                     f64::INFINITY)?;
+            }
+            let value = total / samples.len() as f64;
+            if best.is_none_or(|(_, top)| value > top) {
+                best = Some((*chosen, value));
+            }
+        }
+        best
+    }
+}
+
+/// Searches `state` `depth` plies deep, not counting draws, and returns the best move with
+/// its value. Positions left unfinished at the last ply are scored by `FlagCount`.
+pub fn search(state: &GameState, depth: usize, time_budget: Duration) -> (Move, f64) {
+    search_with(state, depth, time_budget, &FlagCount)
+}
+
+/// Like `search`, scoring unfinished positions with `evaluator`. Depths are searched one after
+/// another from 1 for as long as `time_budget` allows; the first always finishes, however long
+/// it takes.
+///
+/// # Panics
+///
+/// When `state` has no legal move.
+pub fn search_with(state: &GameState,
+                   depth: usize,
+                   time_budget: Duration,
+                   evaluator: &dyn Evaluator)
+                   -> (Move, f64) {
+    let legal: Vec<Move> = state.legal_moves().collect();
+    assert!(!legal.is_empty(), "no legal move to search");
+    let started = Instant::now();
+    let mut rng = SeededRng::new(SEED);
+    let samples: Vec<Node> = (0..SAMPLES).map(|_| Node::deal(state, &mut rng)).collect();
+    let mut searcher = Searcher {
+        evaluator,
+        deadline: None,
+    };
+    let mut best = (legal[0], 0.0);
+    for plies in 1..depth.max(1) + 1 {
+        match searcher.root(&samples, &legal, plies) {
+            Some(found) => best = found,
+            None => break,
+        }
+        searcher.deadline = Some(started + time_budget);
+    }
+    best
+}
+
+#[cfg(test)]
+mod test_search {
+    use super::*;
+    use message_parsing::Color;
+    use test_support::card;
+
+    #[test]
+    fn finds_the_breakthrough() {
+        let state = GameState::from_notation("n red,orange,yellow,green,blue,purple \
+                                              a1a2a3|b1c2d4/b4b5b6|c7d8e9/f7f8|a9b9c9/|/|/\
+                                              |/|/|/| nn------- f9d1")
+            .unwrap();
+        let (chosen, value) = search(&state, 1, Duration::from_secs(10));
+        assert_eq!(Move {
+                       flag: 2,
+                       card: card(Color::Color6, 9),
+                   },
+                   chosen);
+        assert_eq!(1.0, value);
+    }
+
+    #[test]
+    fn sees_the_opponent_reply() {
+        // South holds flags 8 and 9 and is one green 7 away from a wedge on flag 7, which our
+        // red pair cannot beat. One ply deep that goes unnoticed; two plies deep every move
+        // loses.
+        let mut state = GameState::from_notation("n red,orange,yellow,green,blue,purple \
+                                                  |/|/|/|/|/|/a1a2|d5d6/|/| -------ss f1f2")
+            .unwrap();
+        state.deck = vec![card(Color::Color4, 7), card(Color::Color5, 9)];
+        state.opponent_model.hand_size = 2;
+        assert!(search(&state, 1, Duration::from_secs(10)).1 > -1.0);
+        assert_eq!(-1.0, search(&state, 2, Duration::from_secs(10)).1);
+    }
+
+    #[test]
+    fn first_depth_always_finishes() {
+        let state = GameState::from_notation("s red,orange,yellow,green,blue,purple \
+                                              a5|/|/|b2/|/|/|/|/|/| --------- a6c3d9e1f4")
+            .unwrap();
+        let (chosen, _) = search(&state, 6, Duration::from_millis(0));
+        assert!(state.is_legal_move(&chosen));
+    }
+}
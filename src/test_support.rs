@@ -0,0 +1,24 @@
+//! Helpers shared by the unit tests.
+
+use game_state::{AiInterface, GameState, Move};
+use message_parsing::{Card, Color};
+
+/// Always plays the first of `GameState::legal_moves`, so its games are easy to predict.
+pub struct FirstMoveBot;
+
+impl AiInterface for FirstMoveBot {
+    fn choose_move(&self, state: &GameState) -> Option<Move> {
+        state.legal_moves().next()
+    }
+
+    fn get_bot_name(&self) -> String {
+        String::from("first")
+    }
+}
+
+pub fn card(color: Color, number: i32) -> Card {
+    Card {
+        color,
+        number,
+    }
+}
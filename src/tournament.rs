@@ -0,0 +1,380 @@
+use game_state::AiInterface;
+use harness::ProcessSeat;
+use match_runner::{HandlerSeat, MatchResult};
+use message_parsing::Direction;
+use referee::{Referee, Seat};
+use std::fmt;
+use std::io;
+use std::process;
+use std::time::Duration;
+
+const INITIAL_ELO: f64 = 1500.0;
+const ELO_K: f64 = 32.0;
+/// Normal quantile for a 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// A bot taking part in a tournament.
+pub enum Entrant {
+    InProcess {
+        name: String,
+        ai: Box<dyn AiInterface>,
+    },
+    /// An executable speaking the text protocol on stdin and stdout.
+    Process {
+        name: String,
+        program: String,
+        args: Vec<String>,
+    },
+}
+
+impl Entrant {
+    pub fn name(&self) -> &str {
+        match *self {
+            Entrant::InProcess { ref name, .. } => name,
+            Entrant::Process { ref name, .. } => name,
+        }
+    }
+    fn seat(&self, timeout: Duration) -> io::Result<Box<dyn Seat + '_>> {
+        match *self {
+            Entrant::InProcess { ref ai, .. } => Ok(Box::new(HandlerSeat::new(&**ai))),
+            Entrant::Process { ref program, ref args, .. } => {
+                let seat = ProcessSeat::spawn(process::Command::new(program).args(args), timeout)?;
+                Ok(Box::new(seat))
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Pairing {
+    /// Everyone plays everyone once.
+    RoundRobin,
+    /// Each round pairs entrants with similar scores who have not met yet. With an odd number
+    /// of entrants the lowest ranked one who has not sat out yet sits the round out.
+    Swiss { rounds: usize },
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct TournamentConfig {
+    pub pairing: Pairing,
+    /// Games per pairing. Consecutive games share a deal with north and south swapped.
+    pub games_per_pairing: usize,
+    pub seed: u64,
+    /// Per reply limit for process entrants.
+    pub timeout: Duration,
+}
+
+impl Default for TournamentConfig {
+    fn default() -> TournamentConfig {
+        TournamentConfig {
+            pairing: Pairing::RoundRobin,
+            games_per_pairing: 10,
+            seed: 0,
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// One game of the tournament, with entrants given by their index in the entrant list.
+#[derive(PartialEq, Debug, Clone)]
+pub struct GameRecord {
+    pub north: usize,
+    pub south: usize,
+    /// Carries the game's seed, so a surprising game can be replayed with `play_match`.
+    pub result: MatchResult,
+}
+
+impl GameRecord {
+    /// `None` for a draw, like `loser`.
+    pub fn winner(&self) -> Option<usize> {
+        self.result.winner.map(|winner| {
+            match winner {
+                Direction::North => self.north,
+                Direction::South => self.south,
+            }
+        })
+    }
+    pub fn loser(&self) -> Option<usize> {
+        self.result.winner.map(|winner| {
+            match winner {
+                Direction::North => self.south,
+                Direction::South => self.north,
+            }
+        })
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Standing {
+    pub name: String,
+    pub games: usize,
+    pub wins: usize,
+    /// Wilson score interval around the win rate.
+    pub win_rate_low: f64,
+    pub win_rate_high: f64,
+    pub elo: f64,
+}
+
+impl Standing {
+    pub fn win_rate(&self) -> f64 {
+        if self.games == 0 {
+            0.0
+        } else {
+            self.wins as f64 / self.games as f64
+        }
+    }
+}
+
+/// Final table, best Elo first, along with every game played.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Standings {
+    pub rows: Vec<Standing>,
+    pub games: Vec<GameRecord>,
+}
+
+impl fmt::Display for Standings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f,
+                 "{:<4} {:<20} {:>5} {:>5} {:>6} {:>15} {:>6}",
+                 "Rank",
+                 "Name",
+                 "Games",
+                 "Wins",
+                 "Win%",
+                 "95% CI",
+                 "Elo")?;
+        for (rank, row) in self.rows.iter().enumerate() {
+            writeln!(f,
+                     "{:<4} {:<20} {:>5} {:>5} {:>5.1}% {:>15} {:>6.0}",
+                     rank + 1,
+                     row.name,
+                     row.games,
+                     row.wins,
+                     row.win_rate() * 100.0,
+                     format!("[{:.1}, {:.1}]", row.win_rate_low * 100.0, row.win_rate_high * 100.0),
+                     row.elo)?;
+        }
+        Ok(())
+    }
+}
+
+/// 95% Wilson score interval for `wins` out of `games`.
+pub fn wilson_interval(wins: usize, games: usize) -> (f64, f64) {
+    if games == 0 {
+        return (0.0, 1.0);
+    }
+    let n = games as f64;
+    let p = wins as f64 / n;
+    let z2 = Z_95 * Z_95;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let half = Z_95 * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / (1.0 + z2 / n);
+    ((center - half).max(0.0), (center + half).min(1.0))
+}
+
+/// Updates `ratings` for a game in which `player` scored `score` against `opponent`: 1 for a
+/// win, 0.5 for a draw.
+pub fn update_elo(ratings: &mut [f64], player: usize, opponent: usize, score: f64) {
+    let expected = 1.0 / (1.0 + 10f64.powf((ratings[opponent] - ratings[player]) / 400.0));
+    let change = ELO_K * (score - expected);
+    ratings[player] += change;
+    ratings[opponent] -= change;
+}
+
+/// Seed of game `game` between the pair numbered `pairing`. Games come in twos sharing a seed.
+fn game_seed(base: u64, pairing: usize, game: usize) -> u64 {
+    base.wrapping_mul(1_000_003)
+        .wrapping_add((pairing as u64) << 32)
+        .wrapping_add((game / 2) as u64)
+}
+
+fn play_game(entrants: &[Entrant],
+             north: usize,
+             south: usize,
+             seed: u64,
+             config: &TournamentConfig)
+             -> io::Result<GameRecord> {
+    let mut north_seat = entrants[north].seat(config.timeout)?;
+    let mut south_seat = entrants[south].seat(config.timeout)?;
+    let mut referee = Referee::shuffled(seed);
+    let result = referee.run(&mut *north_seat, &mut *south_seat);
+    Ok(GameRecord {
+        north,
+        south,
+        result: MatchResult::from_game(&referee, result),
+    })
+}
+
+/// Pairs entrants for the next Swiss round: by wins, best first, avoiding rematches when
+/// possible. With an odd number of entrants the bye is settled first, going to the lowest
+/// ranked entrant without one in `byes`, or the lowest ranked of all once everyone has had one.
+fn swiss_pairs(wins: &[usize], met: &[Vec<bool>], byes: &mut [bool]) -> Vec<(usize, usize)> {
+    let mut order: Vec<usize> = (0..wins.len()).collect();
+    order.sort_by_key(|i| (usize::MAX - wins[*i], *i));
+    if order.len() % 2 == 1 {
+        let bye = order.iter().rposition(|i| !byes[*i]).unwrap_or(order.len() - 1);
+        byes[order.remove(bye)] = true;
+    }
+    let mut pairs = vec![];
+    while order.len() >= 2 {
+        let first = order.remove(0);
+        let opponent = order.iter().position(|j| !met[first][*j]).unwrap_or(0);
+        pairs.push((first, order.remove(opponent)));
+    }
+    pairs
+}
+
+/// Runs every scheduled game and ranks the entrants. Only failing to start a process entrant
+/// is an error; misbehaving bots lose their games by forfeit.
+pub fn run_tournament(entrants: &[Entrant], config: &TournamentConfig) -> io::Result<Standings> {
+    let count = entrants.len();
+    let mut games: Vec<GameRecord> = vec![];
+    let mut wins = vec![0; count];
+    let mut met = vec![vec![false; count]; count];
+    let mut byes = vec![false; count];
+    // `None` for a round that can only be paired once the rounds before it have been played.
+    let rounds: Vec<Option<Vec<(usize, usize)>>> = match config.pairing {
+        Pairing::RoundRobin => {
+            vec![Some((0..count).flat_map(|i| (i + 1..count).map(move |j| (i, j))).collect())]
+        }
+        Pairing::Swiss { rounds } => vec![None; rounds],
+    };
+    let mut pairing_number = 0;
+    for scheduled in rounds {
+        let pairs = scheduled.unwrap_or_else(|| swiss_pairs(&wins, &met, &mut byes));
+        for (a, b) in pairs {
+            met[a][b] = true;
+            met[b][a] = true;
+            for game in 0..config.games_per_pairing {
+                let (north, south) = if game % 2 == 0 { (a, b) } else { (b, a) };
+                let seed = game_seed(config.seed, pairing_number, game);
+                let record = play_game(entrants, north, south, seed, config)?;
+                if let Some(winner) = record.winner() {
+                    wins[winner] += 1;
+                }
+                games.push(record);
+            }
+            pairing_number += 1;
+        }
+    }
+    let mut ratings = vec![INITIAL_ELO; count];
+    let mut played = vec![0; count];
+    for game in &games {
+        match (game.winner(), game.loser()) {
+            (Some(winner), Some(loser)) => update_elo(&mut ratings, winner, loser, 1.0),
+            _ => update_elo(&mut ratings, game.north, game.south, 0.5),
+        }
+        played[game.north] += 1;
+        played[game.south] += 1;
+    }
+    let mut rows: Vec<Standing> = entrants.iter()
+        .enumerate()
+        .map(|(i, entrant)| {
+            let (low, high) = wilson_interval(wins[i], played[i]);
+            Standing {
+                name: String::from(entrant.name()),
+                games: played[i],
+                wins: wins[i],
+                win_rate_low: low,
+                win_rate_high: high,
+                elo: ratings[i],
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| b.elo.total_cmp(&a.elo));
+    Ok(Standings {
+        rows,
+        games,
+    })
+}
+
+#[cfg(test)]
+mod test_tournament {
+    use super::*;
+    use test_support::FirstMoveBot;
+
+    fn entrants(count: usize) -> Vec<Entrant> {
+        (0..count)
+            .map(|i| {
+                Entrant::InProcess {
+                    name: format!("bot{}", i),
+                    ai: Box::new(FirstMoveBot),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wilson_interval_brackets_the_rate() {
+        let (low, high) = wilson_interval(15, 20);
+        assert!(low < 0.75 && 0.75 < high);
+        assert!((low - 0.531).abs() < 0.001);
+        assert!((high - 0.888).abs() < 0.001);
+        assert_eq!((0.0, 1.0), wilson_interval(0, 0));
+    }
+
+    #[test]
+    fn elo_moves_points_from_loser_to_winner() {
+        let mut ratings = vec![INITIAL_ELO, INITIAL_ELO];
+        update_elo(&mut ratings, 1, 0, 1.0);
+        assert_eq!(1516.0, ratings[1]);
+        assert_eq!(1484.0, ratings[0]);
+        update_elo(&mut ratings, 0, 1, 0.5);
+        assert!(ratings[0] > 1484.0 && ratings[1] < 1516.0);
+    }
+
+    #[test]
+    fn round_robin_plays_every_pair_on_both_sides() {
+        let config = TournamentConfig {
+            games_per_pairing: 4,
+            ..Default::default()
+        };
+        let standings = run_tournament(&entrants(3), &config).unwrap();
+        assert_eq!(12, standings.games.len());
+        assert!(standings.rows.iter().all(|row| row.games == 8));
+        let total: f64 = standings.rows.iter().map(|row| row.elo).sum();
+        assert!((total - 3.0 * INITIAL_ELO).abs() < 1e-6);
+        assert_eq!(standings.games[0].result.seed, standings.games[1].result.seed);
+        assert_eq!(standings.games[0].north, standings.games[1].south);
+        assert_eq!(4, standings.to_string().lines().count());
+        assert!(run_tournament(&entrants(1), &config).unwrap().games.is_empty());
+    }
+
+    #[test]
+    fn swiss_avoids_rematches() {
+        let config = TournamentConfig {
+            pairing: Pairing::Swiss { rounds: 3 },
+            games_per_pairing: 2,
+            ..Default::default()
+        };
+        let standings = run_tournament(&entrants(4), &config).unwrap();
+        assert_eq!(12, standings.games.len());
+        let mut pairs: Vec<(usize, usize)> = standings.games
+            .iter()
+            .map(|g| (g.north.min(g.south), g.north.max(g.south)))
+            .collect();
+        pairs.sort();
+        pairs.dedup();
+        assert_eq!(6, pairs.len());
+    }
+
+    #[test]
+    fn swiss_gives_a_bye_with_odd_entrants() {
+        let met = vec![vec![false; 3]; 3];
+        let mut byes = vec![false; 3];
+        assert_eq!(vec![(2, 1)], swiss_pairs(&[0, 1, 2], &met, &mut byes));
+        assert_eq!(vec![true, false, false], byes);
+        assert_eq!(vec![(2, 0)], swiss_pairs(&[0, 1, 2], &met, &mut byes));
+        assert_eq!(vec![true, true, false], byes);
+    }
+
+    #[test]
+    fn swiss_bye_is_settled_before_avoiding_rematches() {
+        // The leader has already met everyone but the last entrant, who still sits out.
+        let met: Vec<Vec<bool>> = (0..5)
+            .map(|i| (0..5).map(|j| i != j && i.min(j) == 0 && i.max(j) < 4).collect())
+            .collect();
+        let mut byes = vec![false; 5];
+        assert_eq!(vec![(0, 1), (2, 3)], swiss_pairs(&[4, 3, 2, 1, 0], &met, &mut byes));
+        assert!(byes[4]);
+    }
+}
@@ -0,0 +1,244 @@
+use game_state::{AiInterface, GameHandler};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Which way a transcript line travelled, seen from the bot.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Flow {
+    Incoming,
+    Outgoing,
+}
+
+/// One line of a transcript, written as `<millis> < <line>` for lines from the server and
+/// `<millis> > <line>` for replies, where `millis` counts from the Unix epoch.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Entry {
+    pub millis: u64,
+    pub flow: Flow,
+    pub line: String,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let arrow = match self.flow {
+            Flow::Incoming => '<',
+            Flow::Outgoing => '>',
+        };
+        write!(f, "{} {} {}", self.millis, arrow, self.line)
+    }
+}
+
+impl Entry {
+    /// Parses one transcript line, `None` if it is not in the format `Display` writes.
+    pub fn parse(text: &str) -> Option<Entry> {
+        let mut split = text.splitn(3, ' ');
+        let millis = split.next()?.parse::<u64>().ok()?;
+        let flow = match split.next()? {
+            "<" => Flow::Incoming,
+            ">" => Flow::Outgoing,
+            _ => return None,
+        };
+        Some(Entry {
+            millis,
+            flow,
+            line: String::from(split.next().unwrap_or("")),
+        })
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// Writes transcript entries as a `GameHandler` sees lines go by.
+pub struct Recorder {
+    sink: Box<dyn Write + Send>,
+}
+
+impl Recorder {
+    pub fn new(sink: Box<dyn Write + Send>) -> Recorder {
+        Recorder { sink }
+    }
+    /// Appends a line stamped with the current time. Failing to write is ignored so a broken
+    /// transcript never costs the game being played.
+    pub fn record(&mut self, flow: Flow, line: &str) {
+        let entry = Entry {
+            millis: now_millis(),
+            flow,
+            line: String::from(line),
+        };
+        let _ = writeln!(self.sink, "{}", entry).and_then(|_| self.sink.flush());
+    }
+}
+
+/// Reads a transcript written by a `Recorder`, skipping blank lines.
+pub fn read_transcript<R: BufRead>(reader: R) -> io::Result<Vec<Entry>> {
+    let mut entries = vec![];
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Entry::parse(&line) {
+            Some(entry) => entries.push(entry),
+            None => {
+                let message = format!("bad transcript line {}: '{}'", number + 1, line);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// A server line the replayed bot answered differently from the recording.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ReplyDiff {
+    /// Index of the incoming entry in the transcript.
+    pub index: usize,
+    pub incoming: String,
+    pub recorded: Option<String>,
+    pub replayed: Option<String>,
+}
+
+/// Feeds every incoming line of `entries` through `respond` with `ai` and reports the
+/// lines where its reply differs from the recorded one. An empty result means the bot still
+/// plays exactly as it did.
+pub fn replay(entries: &[Entry], ai: &dyn AiInterface) -> Vec<ReplyDiff> {
+    let mut handler = GameHandler::with_output(io::sink());
+    let mut diffs = vec![];
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.flow != Flow::Incoming {
+            continue;
+        }
+        let recorded = entries.get(index + 1)
+            .filter(|next| next.flow == Flow::Outgoing)
+            .map(|next| next.line.clone());
+        let replayed = handler.respond(ai, entry.line.clone())
+            .map(|reply| reply.to_wire(&handler.state));
+        if replayed != recorded {
+            diffs.push(ReplyDiff {
+                index,
+                incoming: entry.line.clone(),
+                recorded,
+                replayed,
+            });
+        }
+    }
+    diffs
+}
+
+#[cfg(test)]
+mod test_transcript {
+    use super::*;
+    use game_state::{GameState, Move};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use test_support::FirstMoveBot;
+
+    /// Same name as `FirstMoveBot`, so only its move differs on replay.
+    struct LastMoveAi {}
+
+    impl AiInterface for LastMoveAi {
+        fn choose_move(&self, state: &GameState) -> Option<Move> {
+            state.legal_moves().last()
+        }
+
+        fn get_bot_name(&self) -> String {
+            String::from("first")
+        }
+    }
+
+    /// A `Write` whose contents can still be read after it is boxed away.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const GAME: [&str; 5] = ["player north name",
+                             "colors a b c d e f",
+                             "player north hand a,1 b,2 c,3",
+                             "flag claim-status unclaimed unclaimed unclaimed unclaimed unclaimed \
+                              unclaimed unclaimed unclaimed unclaimed",
+                             "go play-card"];
+
+    fn record_game() -> Vec<Entry> {
+        let buffer = SharedBuffer::default();
+        let mut handler = GameHandler::with_output(io::sink());
+        handler.record_to(Box::new(buffer.clone()));
+        for line in GAME.iter() {
+            handler.run_one_round(&FirstMoveBot, String::from(*line)).unwrap();
+        }
+        let text = buffer.0.lock().unwrap().clone();
+        read_transcript(&text[..]).unwrap()
+    }
+
+    #[test]
+    fn entry_round_trips() {
+        let entry = Entry {
+            millis: 1500000000123,
+            flow: Flow::Outgoing,
+            line: String::from("play 1 red,3"),
+        };
+        assert_eq!("1500000000123 > play 1 red,3", entry.to_string());
+        assert_eq!(Some(entry.clone()), Entry::parse(&entry.to_string()));
+        assert_eq!(None, Entry::parse("12 ? x"));
+    }
+
+    #[test]
+    fn handler_records_both_directions() {
+        let entries = record_game();
+        assert_eq!(7, entries.len());
+        assert_eq!(Flow::Incoming, entries[0].flow);
+        assert_eq!(Entry {
+                       millis: entries[1].millis,
+                       flow: Flow::Outgoing,
+                       line: String::from("player north first"),
+                   },
+                   entries[1]);
+        assert_eq!("play 1 a,1", entries[6].line);
+        assert!(entries.windows(2).all(|w| w[0].millis <= w[1].millis));
+    }
+
+    #[test]
+    fn recording_handler_can_move_to_another_thread() {
+        let buffer = SharedBuffer::default();
+        let mut handler = GameHandler::with_output(io::sink());
+        handler.record_to(Box::new(buffer.clone()));
+        thread::spawn(move || handler.run_one_round(&FirstMoveBot, String::from(GAME[0])))
+            .join()
+            .unwrap()
+            .unwrap();
+        assert!(!buffer.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn replay_with_same_bot_has_no_diffs() {
+        assert!(replay(&record_game(), &FirstMoveBot).is_empty());
+    }
+
+    #[test]
+    fn replay_with_changed_bot_reports_diffs() {
+        let diffs = replay(&record_game(), &LastMoveAi {});
+        assert_eq!(vec![ReplyDiff {
+                            index: 5,
+                            incoming: String::from("go play-card"),
+                            recorded: Some(String::from("play 1 a,1")),
+                            replayed: Some(String::from("play 9 c,3")),
+                        }],
+                   diffs);
+    }
+
+    #[test]
+    fn malformed_transcript_is_an_error() {
+        assert!(read_transcript(&b"1 < go play-card\nnonsense\n"[..]).is_err());
+    }
+}
@@ -10,4 +10,23 @@ fn confirm_parsing_message() {
     }
 }
 
+struct FirstMoveAi {}
 
+impl rbi::game_state::AiInterface for FirstMoveAi {
+    fn choose_move(&self,
+                         state: &rbi::game_state::GameState)
+                         -> Option<rbi::game_state::Move> {
+        state.legal_moves().next()
+    }
+
+    fn get_bot_name(&self) -> String {
+        String::from("integration_bot")
+    }
+}
+
+#[test]
+fn confirm_match_between_bots() {
+    let result = rbi::match_runner::play_match(&FirstMoveAi {}, &FirstMoveAi {}, 1);
+    assert_eq!(Some("integration_bot"), result.winner_name());
+    assert!(!result.moves.is_empty());
+}